@@ -0,0 +1,40 @@
+use std::{path::Path, time::Instant};
+
+use anyhow::Context;
+
+use kecs::{checksum_set::AuditOptions, ui::UiHandler, util};
+
+use crate::status::Status;
+
+use super::load_checksum_set;
+
+pub fn audit(
+    checksums_path: &Path,
+    root_path: Option<&Path>,
+    threads: Option<usize>,
+    ui: &mut dyn UiHandler,
+) -> Result<Status, anyhow::Error> {
+    let root_path = root_path
+        .or_else(|| checksums_path.parent())
+        .with_context(|| "Could not determine root path.")?;
+
+    let checksums = load_checksum_set(checksums_path, ui)?;
+
+    let mut options = AuditOptions::default();
+
+    if let Some(threads) = threads {
+        options.threads = threads.max(1);
+    }
+
+    let now = Instant::now();
+
+    let report = checksums
+        .audit(root_path, &options, ui)
+        .with_context(|| "Auditing files")?;
+
+    eprintln!("Operation took {}.", util::humanize_duration(now.elapsed()));
+
+    report.print();
+
+    Ok(Status::from_audit(&report))
+}
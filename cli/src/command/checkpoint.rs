@@ -0,0 +1,32 @@
+use std::{fs, io, path::Path, time::Duration};
+
+use tracing::warn;
+
+use kecs::checksum_set::CheckpointOptions;
+
+/// Checkpoint options for a run producing or verifying the specified checksum set,
+/// saving checkpoints next to it, every `interval` seconds if specified.
+pub fn checkpoint_options(checksums_path: &Path, resume: bool, interval: Option<u64>) -> CheckpointOptions {
+    let mut file_name = checksums_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".kecs-checkpoint");
+
+    let mut options = CheckpointOptions {
+        resume,
+        ..CheckpointOptions::new(&checksums_path.with_file_name(file_name))
+    };
+
+    if let Some(interval) = interval {
+        options.interval = Duration::from_secs(interval);
+    }
+
+    options
+}
+
+/// Remove the checkpoint file of a finished run, if one was saved.
+pub fn remove_checkpoint(options: &CheckpointOptions) {
+    match fs::remove_file(&options.path) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => warn!("Removing checkpoint file failed: {}: {err}", options.path.display()),
+    }
+}
@@ -0,0 +1,24 @@
+use std::path::Path;
+
+use kecs::{
+    checksum_set::{ChecksumSetFormat, WriteOptions},
+    ui::UiHandler,
+};
+
+use crate::status::Status;
+
+use super::{load_checksum_set, write_checksum_set};
+
+pub fn convert(
+    input_path: &Path,
+    output_path: &Path,
+    format: Option<ChecksumSetFormat>,
+    options: &WriteOptions,
+    ui: &mut dyn UiHandler,
+) -> Result<Status, anyhow::Error> {
+    let checksums = load_checksum_set(input_path, ui)?;
+
+    write_checksum_set(&checksums, output_path, format, options)?;
+
+    Ok(Status::Success)
+}
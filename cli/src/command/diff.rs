@@ -1,18 +1,90 @@
-use std::path::Path;
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
-use kecs::{checksum_set::ChecksumSet, ui::UiHandler};
+use kecs::{
+    checksum_set::{self, MetadataKind, ReportInfo, VerifyOptions},
+    ui::UiHandler,
+};
 
-pub fn diff(checksums_a_path: &Path, checksums_b_path: &Path, ui: &mut dyn UiHandler) -> Result<(), anyhow::Error> {
-    let checksums_a = ChecksumSet::load_from_file(checksums_a_path, ui)?;
-    let checksums_b = ChecksumSet::load_from_file(checksums_b_path, ui)?;
+use crate::status::Status;
 
-    let diff = checksums_a.diff(&checksums_b, ui)?;
+use super::{load_checksum_set, OutputFormat};
 
-    if diff.is_different() {
-        diff.print();
-    } else {
-        println!("No differences found.");
-    }
+pub struct DiffOptions {
+    pub root_path_a: Option<PathBuf>,
+    pub root_path_b: Option<PathBuf>,
+    pub ignore_metadata: Vec<MetadataKind>,
+    pub threads: Option<usize>,
+}
+
+/// Compare two checksum sets, or a checksum set and a directory.
+///
+/// If either path is a directory, it is compared against the other checksum set's
+/// contents without generating a complete checksum set for it.
+pub fn diff(
+    path_a: &Path,
+    path_b: &Path,
+    options: DiffOptions,
+    output_format: OutputFormat,
+    ui: &mut dyn UiHandler,
+) -> Result<Status, anyhow::Error> {
+    let now = Instant::now();
+
+    let (diff, checksum_sets, root_path) = match (path_a.is_dir(), path_b.is_dir()) {
+        (true, true) => return Err(anyhow::anyhow!("At least one of the paths must be a checksum set.")),
+        (false, false) => {
+            let checksums_a = load_checksum_set(path_a, ui)?;
+            let checksums_b = load_checksum_set(path_b, ui)?;
+
+            let mut diff_options = checksum_set::DiffOptions {
+                root_path_a: options.root_path_a,
+                root_path_b: options.root_path_b,
+                ignore_metadata: options.ignore_metadata.into_iter().collect(),
+                ..Default::default()
+            };
+
+            if let Some(threads) = options.threads {
+                diff_options.threads = threads.max(1);
+            }
+
+            let diff = checksums_a.diff_with_options(&checksums_b, &diff_options, ui)?;
+
+            (diff, vec![path_a, path_b], None)
+        }
+        (a_is_dir, _) => {
+            let (checksums_path, root_path) = if a_is_dir { (path_b, path_a) } else { (path_a, path_b) };
+
+            let checksums = load_checksum_set(checksums_path, ui)?;
+
+            let mut verify_options = VerifyOptions {
+                ignore_metadata: options.ignore_metadata.into_iter().collect(),
+                ..Default::default()
+            };
+
+            if let Some(threads) = options.threads {
+                verify_options.threads = threads.max(1);
+            }
+
+            let diff = checksums.diff_directory(root_path, &verify_options, ui)?;
+            let diff = if a_is_dir { diff.reversed() } else { diff };
+
+            (diff, vec![checksums_path], Some(root_path))
+        }
+    };
+
+    let info = ReportInfo {
+        operation: "diff".to_owned(),
+        checksum_sets: checksum_sets
+            .into_iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect(),
+        root_path: root_path.map(|p| p.to_string_lossy().into_owned()),
+        duration: now.elapsed(),
+    };
+
+    super::print_diff(&diff, output_format, &info, "No differences found.")?;
 
-    Ok(())
+    Ok(Status::from_diff(&diff))
 }
@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use kecs::{
+    checksum_set::{ChecksumSet, LinkMethod},
+    ui::UiHandler,
+};
+
+use crate::status::Status;
+
+use super::load_checksum_set;
+
+pub fn dupes(
+    checksums_paths: &[PathBuf],
+    root_paths: &[PathBuf],
+    link_method: Option<LinkMethod>,
+    ui: &mut dyn UiHandler,
+) -> Result<Status, anyhow::Error> {
+    if !root_paths.is_empty() && root_paths.len() != checksums_paths.len() {
+        return Err(anyhow::anyhow!(
+            "A root path must be specified for each checksum set, or none at all."
+        ));
+    }
+
+    let root_paths: Vec<PathBuf> = if root_paths.is_empty() {
+        checksums_paths
+            .iter()
+            .map(|checksums_path| {
+                checksums_path
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .with_context(|| "Could not determine root path.")
+            })
+            .collect::<Result<_, _>>()?
+    } else {
+        root_paths.to_vec()
+    };
+
+    let mut checksum_sets: Vec<ChecksumSet> = Vec::with_capacity(checksums_paths.len());
+
+    for checksums_path in checksums_paths {
+        checksum_sets.push(load_checksum_set(checksums_path, ui)?);
+    }
+
+    let mut duplicates = ChecksumSet::find_duplicates(&checksum_sets)?;
+
+    duplicates.find_linked_files(&root_paths);
+
+    let names: Vec<String> = checksums_paths.iter().map(|p| p.display().to_string()).collect();
+
+    duplicates.print(&names);
+
+    let Some(link_method) = link_method else {
+        return Ok(Status::Success);
+    };
+
+    let report = duplicates.link(&root_paths, link_method);
+
+    report.print();
+
+    if report.failed_files.is_empty() {
+        Ok(Status::Success)
+    } else {
+        Ok(Status::IoError)
+    }
+}
@@ -0,0 +1,35 @@
+use std::path::Path;
+
+use kecs::{
+    checksum_set::{ChecksumSetFormat, FileSelection, WriteOptions},
+    ui::UiHandler,
+};
+
+use crate::status::Status;
+
+use super::{load_checksum_set, write_checksum_set};
+
+pub fn filter(
+    input_path: &Path,
+    output_path: &Path,
+    format: Option<ChecksumSetFormat>,
+    selection: &FileSelection,
+    backup: bool,
+    ui: &mut dyn UiHandler,
+) -> Result<Status, anyhow::Error> {
+    let checksums = load_checksum_set(input_path, ui)?;
+
+    let checksums = checksums.filter_files(selection)?;
+
+    write_checksum_set(
+        &checksums,
+        output_path,
+        format,
+        &WriteOptions {
+            backup,
+            ..Default::default()
+        },
+    )?;
+
+    Ok(Status::Success)
+}
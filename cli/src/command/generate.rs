@@ -1,21 +1,42 @@
-use std::{borrow::Cow, path::Path, time::Instant};
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use anyhow::Context;
 
 use kecs::{
-    checksum_set::{ChecksumSetBuilder, HashType},
+    checksum_set::{ChecksumSetBuilder, ChecksumSetFormat, FileFilter, HashType, MetadataKind, WriteOptions},
     ui::UiHandler,
     util,
 };
 
+use crate::status::Status;
+
+use super::{checkpoint_options, is_stdio, remove_checkpoint, write_checksum_set};
+
+pub struct GenerateOptions {
+    pub format: Option<ChecksumSetFormat>,
+    pub hash_types: Vec<HashType>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub ignore_files: Vec<PathBuf>,
+    pub metadata: Vec<MetadataKind>,
+    pub follow_symlinks: bool,
+    pub threads: Option<usize>,
+    pub backup: bool,
+    pub resume: bool,
+    pub checkpoint_interval: Option<u64>,
+}
+
 pub fn generate(
     path: &Path,
     output_file: Option<&Path>,
     root_path: Option<&Path>,
-    hash_type: Option<HashType>,
+    options: GenerateOptions,
     ui: &mut dyn UiHandler,
-) -> Result<(), anyhow::Error> {
-    let hash_type = hash_type.unwrap_or(HashType::Sha256);
+) -> Result<Status, anyhow::Error> {
     let path = util::normalize_path(path);
 
     let output_file: Cow<Path> = output_file
@@ -26,18 +47,96 @@ pub fn generate(
         })
         .with_context(|| "Could not determine output file path.")?;
 
+    let format = options
+        .format
+        .or_else(|| ChecksumSetFormat::from_path(&output_file))
+        .unwrap_or(ChecksumSetFormat::Kecs);
+
+    // If no hash type was specified, use the one implied by the output format or file name if any,
+    // such as for SFV, SHA256SUMS or MD5SUMS.
+    let hash_types = if options.hash_types.is_empty() {
+        let hash_type = format
+            .implied_hash_type()
+            .or_else(|| {
+                output_file
+                    .file_name()
+                    .and_then(|n| HashType::from_filename(&n.to_string_lossy()))
+                    .filter(|_| format != ChecksumSetFormat::Kecs)
+            })
+            .unwrap_or(HashType::Sha256);
+
+        vec![hash_type]
+    } else {
+        options.hash_types
+    };
+
     let root_path = root_path.or_else(|| path.parent()).unwrap_or(&path);
 
     let now = Instant::now();
 
-    let checksum_set = ChecksumSetBuilder::new(hash_type, root_path)
+    let mut filter = FileFilter {
+        include: options.include,
+        exclude: options.exclude,
+        ..Default::default()
+    };
+
+    for ignore_file in options.ignore_files.iter() {
+        filter.add_ignore_file(ignore_file, root_path)?;
+    }
+
+    let mut builder = ChecksumSetBuilder::new(&hash_types, root_path);
+
+    builder.filter(filter)?;
+    builder.metadata(&options.metadata);
+    builder.follow_symlinks(options.follow_symlinks);
+
+    // There is nowhere to save checkpoints when writing to standard output.
+    let checkpoint = if is_stdio(&output_file) {
+        if options.resume {
+            return Err(anyhow::anyhow!("Cannot resume when writing to standard output."));
+        }
+
+        None
+    } else {
+        Some(checkpoint_options(
+            &output_file,
+            options.resume,
+            options.checkpoint_interval,
+        ))
+    };
+
+    if let Some(checkpoint) = checkpoint.as_ref() {
+        builder.checkpoint(checkpoint.clone());
+    }
+
+    if let Some(threads) = options.threads {
+        builder.threads(threads);
+    }
+
+    let checksum_set = builder
         .add_path(&path, ui)
         .build(ui)
         .with_context(|| format!("Generating checksum set for path: {}", path.display()))?;
 
     eprintln!("Operation took {}.", util::humanize_duration(now.elapsed()));
 
-    checksum_set.write_file(&output_file)?;
+    write_checksum_set(
+        &checksum_set,
+        &output_file,
+        Some(format),
+        &WriteOptions {
+            backup: options.backup,
+            ..Default::default()
+        },
+    )?;
+
+    if let Some(checkpoint) = checkpoint.as_ref() {
+        remove_checkpoint(checkpoint);
+    }
 
-    Ok(())
+    if checksum_set.unreadable_files.is_empty() {
+        Ok(Status::Success)
+    } else {
+        Ok(Status::IoError)
+    }
 }
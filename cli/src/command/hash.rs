@@ -0,0 +1,55 @@
+use std::{io, path::PathBuf};
+
+use kecs::checksum_set::{format_sum_line, HashType};
+
+use crate::status::Status;
+
+use super::is_stdio;
+
+/// Hash files or standard input, printing their hashes in GNU coreutils format like `sha256sum`.
+///
+/// Standard input is hashed if no files are specified, or for a file named `-`.
+/// BSD-style tagged format is used if `tag` is set, or if several hash types are specified,
+/// so that the hash type of each line can be told apart.
+/// Files that cannot be read are reported, and the remaining files are still hashed.
+pub fn hash(paths: &[PathBuf], hash_types: &[HashType], tag: bool) -> Result<Status, anyhow::Error> {
+    let stdin_path = [PathBuf::from("-")];
+    let paths = if paths.is_empty() { &stdin_path[..] } else { paths };
+
+    let hash_types = if hash_types.is_empty() {
+        &[HashType::Sha256][..]
+    } else {
+        hash_types
+    };
+
+    let bsd = tag || hash_types.len() > 1;
+
+    let mut status = Status::Success;
+
+    for path in paths {
+        let hashes = if is_stdio(path) {
+            HashType::hash_reader_many(hash_types, io::stdin().lock())
+                .map_err(|err| format!("Reading standard input: {err}"))
+        } else {
+            HashType::hash_many(hash_types, path, |_| {}).map_err(|err| err.to_string())
+        };
+
+        let hashes = match hashes {
+            Ok(hashes) => hashes,
+            Err(err) => {
+                eprintln!("{err}");
+                status = Status::IoError;
+
+                continue;
+            }
+        };
+
+        let name = path.to_string_lossy();
+
+        for (hash_type, hash) in hash_types.iter().zip(hashes.iter()) {
+            println!("{}", format_sum_line(&name, hash, *hash_type, bsd));
+        }
+    }
+
+    Ok(status)
+}
@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+use kecs::{checksum_set::HashType, ui::UiHandler};
+
+use crate::status::Status;
+
+use super::load_checksum_set;
+
+pub fn info(
+    checksums_path: &Path,
+    hash_type: Option<HashType>,
+    depth: Option<usize>,
+    ui: &mut dyn UiHandler,
+) -> Result<Status, anyhow::Error> {
+    let checksums = load_checksum_set(checksums_path, ui)?;
+
+    let hash_types: Vec<String> = checksums.hash_types.iter().map(|ht| ht.to_string()).collect();
+    let total_size: u64 = checksums.files.values().filter_map(|fi| fi.size).sum();
+
+    println!("Hash types: {}", hash_types.join(", "));
+    println!("Files: {}", checksums.files.len());
+    println!("Total size: {} bytes", total_size);
+
+    if !checksums.unreadable_files.is_empty() {
+        println!("Unreadable files: {}", checksums.unreadable_files.len());
+    }
+
+    if let Some(entries) = &checksums.entries {
+        println!("Symlinks, directories and special files: {}", entries.len());
+    }
+
+    if !checksums.metadata.is_empty() {
+        let metadata: Vec<String> = checksums.metadata.iter().map(|k| k.to_string()).collect();
+
+        println!("Metadata: {}", metadata.join(", "));
+    }
+
+    let tree_hash = checksums.tree_hash(hash_type).with_context(|| "Computing tree hash")?;
+
+    println!("Tree hash ({}): {}", tree_hash.hash_type, tree_hash.root);
+
+    if let Some(depth) = depth {
+        println!();
+        println!("-- DIRECTORIES --");
+
+        for (path, hash) in tree_hash.directories.iter() {
+            let (path, path_depth) = if path.is_empty() {
+                (".", 0)
+            } else {
+                (path.as_str(), path.split('/').count())
+            };
+
+            if path_depth <= depth {
+                println!("{}  {}", hash, path);
+            }
+        }
+    }
+
+    Ok(Status::Success)
+}
@@ -0,0 +1,32 @@
+use std::{
+    io,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use kecs::{checksum_set::ChecksumSet, ui::UiHandler};
+
+/// Whether a checksum set has already been read from standard input.
+static STDIN_READ: AtomicBool = AtomicBool::new(false);
+
+/// Determine whether a path refers to standard input or output.
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Load a checksum set from a file, or from standard input if the path is `-`.
+///
+/// Standard input can only be read once, so only one of a command's checksum sets may come from it.
+pub fn load_checksum_set(path: &Path, ui: &mut dyn UiHandler) -> Result<ChecksumSet, anyhow::Error> {
+    if !is_stdio(path) {
+        return Ok(ChecksumSet::load_from_file(path, ui)?);
+    }
+
+    if STDIN_READ.swap(true, Ordering::SeqCst) {
+        return Err(anyhow::anyhow!(
+            "Only one checksum set can be read from standard input."
+        ));
+    }
+
+    Ok(ChecksumSet::load_from_reader(io::stdin().lock(), path, ui)?)
+}
@@ -0,0 +1,48 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use kecs::{
+    checksum_set::{ChecksumSet, ChecksumSetFormat, MergeOptions, WriteOptions},
+    ui::UiHandler,
+};
+
+use crate::status::Status;
+
+use super::{load_checksum_set, write_checksum_set};
+
+pub fn merge(
+    input_paths: &[PathBuf],
+    output_path: &Path,
+    format: Option<ChecksumSetFormat>,
+    options: &MergeOptions,
+    backup: bool,
+    ui: &mut dyn UiHandler,
+) -> Result<Status, anyhow::Error> {
+    let mut checksums: Option<ChecksumSet> = None;
+
+    for input_path in input_paths {
+        let other = load_checksum_set(input_path, ui)?;
+
+        checksums = Some(match checksums {
+            Some(checksums) => checksums
+                .merge(other, options)
+                .with_context(|| format!("Merging checksum set: {}", input_path.display()))?,
+            None => other,
+        });
+    }
+
+    let checksums = checksums.with_context(|| "No checksum sets to merge.")?;
+
+    write_checksum_set(
+        &checksums,
+        output_path,
+        format,
+        &WriteOptions {
+            backup,
+            ..Default::default()
+        },
+    )?;
+
+    Ok(Status::Success)
+}
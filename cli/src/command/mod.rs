@@ -1,7 +1,33 @@
+mod audit;
+mod checkpoint;
+mod convert;
 mod diff;
+mod dupes;
+mod filter;
 mod generate;
+mod hash;
+mod info;
+mod input;
+mod merge;
+mod output;
+mod reroot;
+mod subtract;
+mod update;
 mod verify;
 
+pub use self::audit::*;
+pub use self::checkpoint::*;
+pub use self::convert::*;
 pub use self::diff::*;
+pub use self::dupes::*;
+pub use self::filter::*;
 pub use self::generate::*;
+pub use self::hash::*;
+pub use self::info::*;
+pub use self::input::*;
+pub use self::merge::*;
+pub use self::output::*;
+pub use self::reroot::*;
+pub use self::subtract::*;
+pub use self::update::*;
 pub use self::verify::*;
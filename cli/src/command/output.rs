@@ -0,0 +1,83 @@
+use std::{io, path::Path};
+
+use clap::ValueEnum;
+
+use kecs::checksum_set::{ChecksumSet, ChecksumSetDiff, ChecksumSetFormat, ReportInfo, WriteOptions};
+
+use super::is_stdio;
+
+/// Format of the results printed by commands producing a diff.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text.
+    #[default]
+    Text,
+    /// A single JSON document.
+    Json,
+    /// Newline-delimited JSON, with one line per file followed by a summary line.
+    Ndjson,
+}
+
+/// Print a diff in the specified output format.
+///
+/// `ok_message` is printed instead of the diff in text format, if there are no differences.
+pub fn print_diff(
+    diff: &ChecksumSetDiff,
+    format: OutputFormat,
+    info: &ReportInfo,
+    ok_message: &str,
+) -> Result<(), anyhow::Error> {
+    match format {
+        OutputFormat::Text => {
+            if let Some(notice) = diff.rehash_notice() {
+                println!();
+                println!("{notice}");
+
+                if !diff.is_different() {
+                    println!();
+                }
+            }
+
+            if diff.is_different() {
+                diff.print();
+            } else {
+                println!("{ok_message}");
+            }
+        }
+        OutputFormat::Json => diff.write_json(info, io::stdout().lock())?,
+        OutputFormat::Ndjson => diff.write_ndjson(info, io::stdout().lock())?,
+    }
+
+    Ok(())
+}
+
+/// Write the resulting checksum set of an operation, in the specified format
+/// or one determined by the output file name.
+///
+/// The checksum set is written to standard output if the output path is `-`.
+pub fn write_checksum_set(
+    checksums: &ChecksumSet,
+    output_path: &Path,
+    format: Option<ChecksumSetFormat>,
+    options: &WriteOptions,
+) -> Result<(), anyhow::Error> {
+    let format = format
+        .or_else(|| ChecksumSetFormat::from_path(output_path))
+        .unwrap_or(ChecksumSetFormat::Kecs);
+
+    if is_stdio(output_path) {
+        if options.backup {
+            return Err(anyhow::anyhow!("Cannot keep a backup when writing to standard output."));
+        }
+
+        checksums.write_to(io::stdout().lock(), output_path, format, options.hash_type)?;
+
+        return Ok(());
+    }
+
+    if let Some(backup_path) = checksums.write_file_as(output_path, format, options)? {
+        eprintln!("Previous version kept as: {}", backup_path.display());
+    }
+
+    Ok(())
+}
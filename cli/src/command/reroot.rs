@@ -0,0 +1,36 @@
+use std::path::Path;
+
+use kecs::{
+    checksum_set::{ChecksumSetFormat, WriteOptions},
+    ui::UiHandler,
+};
+
+use crate::status::Status;
+
+use super::{load_checksum_set, write_checksum_set};
+
+pub fn reroot(
+    input_path: &Path,
+    output_path: &Path,
+    format: Option<ChecksumSetFormat>,
+    strip_prefix: &str,
+    add_prefix: &str,
+    backup: bool,
+    ui: &mut dyn UiHandler,
+) -> Result<Status, anyhow::Error> {
+    let checksums = load_checksum_set(input_path, ui)?;
+
+    let checksums = checksums.reroot(strip_prefix, add_prefix)?;
+
+    write_checksum_set(
+        &checksums,
+        output_path,
+        format,
+        &WriteOptions {
+            backup,
+            ..Default::default()
+        },
+    )?;
+
+    Ok(Status::Success)
+}
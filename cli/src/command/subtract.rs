@@ -0,0 +1,37 @@
+use std::path::Path;
+
+use kecs::{
+    checksum_set::{ChecksumSetFormat, WriteOptions},
+    ui::UiHandler,
+};
+
+use crate::status::Status;
+
+use super::{load_checksum_set, write_checksum_set};
+
+pub fn subtract(
+    path_a: &Path,
+    path_b: &Path,
+    output_path: &Path,
+    format: Option<ChecksumSetFormat>,
+    allow_mixed_hash_types: bool,
+    backup: bool,
+    ui: &mut dyn UiHandler,
+) -> Result<Status, anyhow::Error> {
+    let checksums_a = load_checksum_set(path_a, ui)?;
+    let checksums_b = load_checksum_set(path_b, ui)?;
+
+    let checksums = checksums_a.subtract(&checksums_b, allow_mixed_hash_types)?;
+
+    write_checksum_set(
+        &checksums,
+        output_path,
+        format,
+        &WriteOptions {
+            backup,
+            ..Default::default()
+        },
+    )?;
+
+    Ok(Status::Success)
+}
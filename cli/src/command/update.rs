@@ -0,0 +1,70 @@
+use std::{path::Path, time::Instant};
+
+use anyhow::Context;
+
+use kecs::{
+    checksum_set::{ChecksumSet, ChecksumSetFormat, UpdateOptions, WriteOptions},
+    ui::UiHandler,
+    util,
+};
+
+use crate::status::Status;
+
+use super::write_checksum_set;
+
+pub fn update(
+    checksums_path: &Path,
+    root_path: Option<&Path>,
+    threads: Option<usize>,
+    backup: bool,
+    ui: &mut dyn UiHandler,
+) -> Result<Status, anyhow::Error> {
+    // Other formats do not record modification times, so every file would be rehashed on every update.
+    if ChecksumSetFormat::from_path(checksums_path).is_some_and(|format| format != ChecksumSetFormat::Kecs) {
+        return Err(anyhow::anyhow!(
+            "Only KeCS checksum sets can be updated. Convert it to one using 'kecs convert' first."
+        ));
+    }
+
+    let root_path = root_path
+        .or_else(|| checksums_path.parent())
+        .with_context(|| "Could not determine root path.")?;
+
+    let mut checksums = ChecksumSet::load_from_file(checksums_path, ui)?;
+
+    let mut options = UpdateOptions::default();
+
+    if let Some(threads) = threads {
+        options.threads = threads.max(1);
+    }
+
+    let now = Instant::now();
+
+    let update = checksums
+        .update(root_path, &options, ui)
+        .with_context(|| "Updating checksum set")?;
+
+    eprintln!("Operation took {}.", util::humanize_duration(now.elapsed()));
+
+    write_checksum_set(
+        &checksums,
+        checksums_path,
+        None,
+        &WriteOptions {
+            backup,
+            ..Default::default()
+        },
+    )?;
+
+    if update.is_changed() {
+        update.print();
+    } else {
+        println!("No changes found.");
+    }
+
+    if update.unreadable_files.is_empty() {
+        Ok(Status::Success)
+    } else {
+        Ok(Status::IoError)
+    }
+}
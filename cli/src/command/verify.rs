@@ -2,26 +2,96 @@ use std::{path::Path, time::Instant};
 
 use anyhow::Context;
 
-use kecs::{checksum_set::ChecksumSet, ui::UiHandler, util};
+use kecs::{
+    checksum_set::{self, HashType, MetadataKind, ReportInfo},
+    ui::UiHandler,
+    util,
+};
 
-pub fn verify(checksums_path: &Path, root_path: Option<&Path>, ui: &mut dyn UiHandler) -> Result<(), anyhow::Error> {
+use crate::status::Status;
+
+use super::{checkpoint_options, is_stdio, load_checksum_set, remove_checkpoint, OutputFormat};
+
+pub struct VerifyOptions {
+    pub hash_types: Vec<HashType>,
+    pub hash_additional: bool,
+    pub ignore_metadata: Vec<MetadataKind>,
+    pub threads: Option<usize>,
+    pub checkpoint: bool,
+    pub checkpoint_interval: Option<u64>,
+    pub resume: bool,
+}
+
+pub fn verify(
+    checksums_path: &Path,
+    root_path: Option<&Path>,
+    options: VerifyOptions,
+    output_format: OutputFormat,
+    ui: &mut dyn UiHandler,
+) -> Result<Status, anyhow::Error> {
     let root_path = root_path
         .or_else(|| checksums_path.parent())
         .with_context(|| "Could not determine root path.")?;
 
-    let checksums = ChecksumSet::load_from_file(checksums_path, ui)?;
+    // Checkpoints are only saved when requested, as verifying does not otherwise write
+    // anything next to the checksum set, which may be on read-only media.
+    let checkpoint = options.checkpoint || options.resume || options.checkpoint_interval.is_some();
+
+    // There is nowhere to save checkpoints for a checksum set read from standard input.
+    let checkpoint = if !checkpoint {
+        None
+    } else if is_stdio(checksums_path) {
+        return Err(anyhow::anyhow!(
+            "Cannot save checkpoints or resume when reading the checksum set from standard input."
+        ));
+    } else {
+        Some(checkpoint_options(
+            checksums_path,
+            options.resume,
+            options.checkpoint_interval,
+        ))
+    };
+
+    let checksums = load_checksum_set(checksums_path, ui)?;
+
+    let mut verify_options = checksum_set::VerifyOptions {
+        hash_types: options.hash_types,
+        hash_additional_files: options.hash_additional,
+        ignore_metadata: options.ignore_metadata.into_iter().collect(),
+        checkpoint,
+        ..Default::default()
+    };
+
+    if let Some(threads) = options.threads {
+        verify_options.threads = threads.max(1);
+    }
 
     let now = Instant::now();
 
-    let diff = checksums.verify(root_path, ui).with_context(|| "Verifying files")?;
+    let diff = checksums
+        .verify(root_path, &verify_options, ui)
+        .with_context(|| "Verifying files")?;
 
-    eprintln!("Operation took {}.", util::humanize_duration(now.elapsed()));
+    let duration = now.elapsed();
 
-    if diff.is_different() {
-        diff.print();
-    } else {
-        println!("Verified OK.");
+    eprintln!("Operation took {}.", util::humanize_duration(duration));
+
+    let info = ReportInfo {
+        operation: "verify".to_owned(),
+        checksum_sets: vec![checksums_path.to_string_lossy().into_owned()],
+        root_path: Some(if root_path.as_os_str().is_empty() {
+            ".".to_owned()
+        } else {
+            root_path.to_string_lossy().into_owned()
+        }),
+        duration,
+    };
+
+    super::print_diff(&diff, output_format, &info, "Verified OK.")?;
+
+    if let Some(checkpoint) = verify_options.checkpoint.as_ref() {
+        remove_checkpoint(checkpoint);
     }
 
-    Ok(())
+    Ok(Status::from_diff(&diff))
 }
@@ -1,15 +1,19 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::ExitCode};
 
 use clap::Parser;
 
 mod command;
+mod status;
 mod ui;
 
-use kecs::checksum_set::HashType;
+use kecs::checksum_set::{
+    ChecksumSetFormat, FileFilter, FileSelection, HashType, LinkMethod, MergeConflict, MergeOptions, MetadataKind,
+    WriteOptions,
+};
 use tracing::debug;
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
-use crate::ui::fancy::FancyUiHandler;
+use crate::{command::OutputFormat, status::Status, ui::fancy::FancyUiHandler};
 
 #[derive(Debug, Parser)]
 #[clap(name = "KeCS", version = env!("CARGO_PKG_VERSION"), author = env!("CARGO_PKG_AUTHORS"))]
@@ -26,23 +30,249 @@ enum Command {
         path: PathBuf,
         #[clap(long = "root-path", short = 'r', help = "Root path")]
         root_path: Option<PathBuf>,
-        #[clap(long = "output", short = 'o', help = "Output file path")]
+        #[clap(long = "output", short = 'o', help = "Output file path, or - for standard output")]
         output_file: Option<PathBuf>,
-        #[clap(long = "hash-type", short = 't', help = "Specify hash type")]
+        #[clap(
+            long = "format",
+            short = 'f',
+            help = "Output format: kecs, gnu, bsd, sfv or hashdeep (defaults to guessing from the output file name)"
+        )]
+        format: Option<ChecksumSetFormat>,
+        #[clap(
+            long = "hash-type",
+            short = 't',
+            value_delimiter = ',',
+            help = "Specify hash type (may be repeated or comma-separated to generate several at once)"
+        )]
+        hash_types: Vec<HashType>,
+        #[clap(
+            long = "include",
+            help = "Only include files matching the specified glob pattern (may be repeated)"
+        )]
+        include: Vec<String>,
+        #[clap(
+            long = "exclude",
+            help = "Exclude files and directories matching the specified glob pattern (may be repeated)"
+        )]
+        exclude: Vec<String>,
+        #[clap(
+            long = "ignore-file",
+            help = "Exclude files matching the patterns in the specified .gitignore-style file (may be repeated)"
+        )]
+        ignore_files: Vec<PathBuf>,
+        #[clap(
+            long = "metadata",
+            value_delimiter = ',',
+            help = "Capture the specified kinds of metadata for comparison: mtime, mode, owner or xattrs (may be repeated or comma-separated)"
+        )]
+        metadata: Vec<MetadataKind>,
+        #[clap(
+            long = "follow-symlinks",
+            help = "Follow symlinks and hash the files they point to, instead of recording the symlinks themselves"
+        )]
+        follow_symlinks: bool,
+        #[clap(
+            long = "threads",
+            short = 'j',
+            help = "Number of files to hash concurrently (defaults to available parallelism)"
+        )]
+        threads: Option<usize>,
+        #[clap(
+            long = "backup",
+            help = "Keep the previous version of the output file, if any, with a timestamp suffix"
+        )]
+        backup: bool,
+        #[clap(
+            long = "resume",
+            help = "Resume an interrupted run from its checkpoint, skipping files already hashed"
+        )]
+        resume: bool,
+        #[clap(
+            long = "checkpoint-interval",
+            value_name = "SECONDS",
+            help = "Minimum time between saving checkpoints, in seconds (defaults to 60)"
+        )]
+        checkpoint_interval: Option<u64>,
+    },
+
+    #[clap(about = "Print the hashes of files or standard input, like sha256sum")]
+    Hash {
+        #[clap(help = "Files to hash, or - for standard input (defaults to standard input)")]
+        paths: Vec<PathBuf>,
+        #[clap(
+            long = "hash-type",
+            short = 't',
+            value_delimiter = ',',
+            help = "Specify hash type (may be repeated or comma-separated to print several at once, defaults to sha256)"
+        )]
+        hash_types: Vec<HashType>,
+        #[clap(
+            long = "tag",
+            help = "Print BSD-style tagged lines, such as SHA256 (file) = hash (implied by several hash types)"
+        )]
+        tag: bool,
+    },
+
+    #[clap(about = "Convert a checksum set to a different format")]
+    Convert {
+        #[clap(help = "Checksum set to convert, or - for standard input")]
+        input_path: PathBuf,
+        #[clap(help = "Output file path, or - for standard output")]
+        output_path: PathBuf,
+        #[clap(
+            long = "format",
+            short = 'f',
+            help = "Output format: kecs, gnu, bsd, sfv or hashdeep (defaults to guessing from the output file name)"
+        )]
+        format: Option<ChecksumSetFormat>,
+        #[clap(
+            long = "hash-type",
+            short = 't',
+            help = "Hash type to write, for formats only supporting a single one"
+        )]
         hash_type: Option<HashType>,
+        #[clap(
+            long = "backup",
+            help = "Keep the previous version of the output file, if any, with a timestamp suffix"
+        )]
+        backup: bool,
     },
 
-    #[clap(about = "Compare differences between two checksum sets")]
+    #[clap(about = "Compare differences between two checksum sets, or a checksum set and a directory")]
     Diff {
-        #[clap(help = "Checksum set to compare")]
-        checksums_a_path: PathBuf,
-        #[clap(help = "Checksum set to compare with")]
-        checksums_b_path: PathBuf,
+        #[clap(help = "Checksum set or directory to compare, or - for standard input")]
+        path_a: PathBuf,
+        #[clap(help = "Checksum set or directory to compare with, or - for standard input")]
+        path_b: PathBuf,
+        #[clap(
+            long = "root-a",
+            help = "Root path of the first checksum set's files, used to rehash them if the checksum sets have no hash types in common"
+        )]
+        root_path_a: Option<PathBuf>,
+        #[clap(
+            long = "root-b",
+            help = "Root path of the second checksum set's files, used to rehash them if the checksum sets have no hash types in common"
+        )]
+        root_path_b: Option<PathBuf>,
+        #[clap(
+            long = "ignore-metadata",
+            value_delimiter = ',',
+            help = "Do not compare the specified kinds of metadata (mtime, mode, owner or xattrs), even if captured"
+        )]
+        ignore_metadata: Vec<MetadataKind>,
+        #[clap(
+            long = "threads",
+            short = 'j',
+            help = "Number of files to hash concurrently when hashing is necessary (defaults to available parallelism)"
+        )]
+        threads: Option<usize>,
+        #[clap(
+            long = "format",
+            value_enum,
+            default_value_t = OutputFormat::Text,
+            help = "Output format: text, json or ndjson"
+        )]
+        output_format: OutputFormat,
     },
 
     #[clap(about = "Verify checksums")]
     Verify {
-        #[clap(help = "Path to checksum set file to verify")]
+        #[clap(help = "Path to checksum set file to verify, or - for standard input")]
+        checksums_path: PathBuf,
+        #[clap(
+            long = "root-path",
+            short = 'r',
+            help = "Specify root path (defaults to parent directory of checksum file, or the current directory for standard input)"
+        )]
+        root_path: Option<PathBuf>,
+        #[clap(
+            long = "hash-type",
+            short = 't',
+            value_delimiter = ',',
+            help = "Only verify the specified hash types (defaults to all hash types in the checksum set)"
+        )]
+        hash_types: Vec<HashType>,
+        #[clap(
+            long = "hash-additional",
+            help = "Hash additional files to detect whether they are missing files that have been moved"
+        )]
+        hash_additional: bool,
+        #[clap(
+            long = "ignore-metadata",
+            value_delimiter = ',',
+            help = "Do not compare the specified kinds of metadata (mtime, mode, owner or xattrs), even if captured"
+        )]
+        ignore_metadata: Vec<MetadataKind>,
+        #[clap(
+            long = "format",
+            value_enum,
+            default_value_t = OutputFormat::Text,
+            help = "Output format: text, json or ndjson"
+        )]
+        output_format: OutputFormat,
+        #[clap(
+            long = "threads",
+            short = 'j',
+            help = "Number of files to hash concurrently (defaults to available parallelism)"
+        )]
+        threads: Option<usize>,
+        #[clap(
+            long = "checkpoint",
+            help = "Save checkpoints while verifying, so that an interrupted run can be resumed"
+        )]
+        checkpoint: bool,
+        #[clap(
+            long = "resume",
+            help = "Resume an interrupted run from its checkpoint, skipping files already hashed (implies --checkpoint)"
+        )]
+        resume: bool,
+        #[clap(
+            long = "checkpoint-interval",
+            value_name = "SECONDS",
+            help = "Minimum time between saving checkpoints, in seconds (defaults to 60, implies --checkpoint)"
+        )]
+        checkpoint_interval: Option<u64>,
+    },
+
+    #[clap(about = "Audit files against a checksum set, matching files regardless of their path")]
+    Audit {
+        #[clap(help = "Path to checksum set file to audit against, or - for standard input")]
+        checksums_path: PathBuf,
+        #[clap(
+            long = "root-path",
+            short = 'r',
+            help = "Specify root path (defaults to parent directory of checksum file, or the current directory for standard input)"
+        )]
+        root_path: Option<PathBuf>,
+        #[clap(
+            long = "threads",
+            short = 'j',
+            help = "Number of files to hash concurrently (defaults to available parallelism)"
+        )]
+        threads: Option<usize>,
+    },
+
+    #[clap(about = "Show information about a checksum set, including its tree hash")]
+    Info {
+        #[clap(help = "Path to checksum set file")]
+        checksums_path: PathBuf,
+        #[clap(
+            long = "hash-type",
+            short = 't',
+            help = "Hash type to compute the tree hash with (defaults to the checksum set's preferred hash type)"
+        )]
+        hash_type: Option<HashType>,
+        #[clap(
+            long = "depth",
+            short = 'd',
+            help = "Also show the subtree hashes of directories, down to the specified depth"
+        )]
+        depth: Option<usize>,
+    },
+
+    #[clap(about = "Update a checksum set, only hashing new or modified files")]
+    Update {
+        #[clap(help = "Path to KeCS checksum set file to update")]
         checksums_path: PathBuf,
         #[clap(
             long = "root-path",
@@ -50,11 +280,171 @@ enum Command {
             help = "Specify root path (defaults to parent directory of checksum file)"
         )]
         root_path: Option<PathBuf>,
+        #[clap(
+            long = "threads",
+            short = 'j',
+            help = "Number of files to hash concurrently (defaults to available parallelism)"
+        )]
+        threads: Option<usize>,
+        #[clap(
+            long = "backup",
+            help = "Keep the previous version of the output file, if any, with a timestamp suffix"
+        )]
+        backup: bool,
+    },
+
+    #[clap(about = "Find duplicate files in one or more checksum sets")]
+    Dupes {
+        #[clap(required = true, help = "Checksum sets to search for duplicates")]
+        checksums_paths: Vec<PathBuf>,
+        #[clap(
+            long = "root-path",
+            short = 'r',
+            help = "Specify root path of each checksum set, in the same order (may be repeated, defaults to parent directory of each checksum file)"
+        )]
+        root_paths: Vec<PathBuf>,
+        #[clap(
+            long = "link",
+            help = "Replace duplicates with links to a single file after confirming their contents are identical: hardlink or reflink"
+        )]
+        link: Option<LinkMethod>,
+    },
+
+    #[clap(about = "Merge several checksum sets into one")]
+    Merge {
+        #[clap(required = true, help = "Checksum sets to merge")]
+        input_paths: Vec<PathBuf>,
+        #[clap(long = "output", short = 'o', help = "Output file path, or - for standard output")]
+        output_path: PathBuf,
+        #[clap(
+            long = "format",
+            short = 'f',
+            help = "Output format: kecs, gnu, bsd, sfv or hashdeep (defaults to guessing from the output file name)"
+        )]
+        format: Option<ChecksumSetFormat>,
+        #[clap(
+            long = "on-conflict",
+            default_value_t = MergeConflict::Fail,
+            help = "How to resolve files with different contents in several checksum sets: fail, first or last"
+        )]
+        on_conflict: MergeConflict,
+        #[clap(
+            long = "allow-mixed-hash-types",
+            help = "Allow checksum sets with different hash types, keeping only the hash types present in both"
+        )]
+        allow_mixed_hash_types: bool,
+        #[clap(
+            long = "backup",
+            help = "Keep the previous version of the output file, if any, with a timestamp suffix"
+        )]
+        backup: bool,
+    },
+
+    #[clap(about = "Remove the files present with the same contents in another checksum set")]
+    Subtract {
+        #[clap(help = "Checksum set to remove files from")]
+        path_a: PathBuf,
+        #[clap(help = "Checksum set containing the files to remove")]
+        path_b: PathBuf,
+        #[clap(long = "output", short = 'o', help = "Output file path, or - for standard output")]
+        output_path: PathBuf,
+        #[clap(
+            long = "format",
+            short = 'f',
+            help = "Output format: kecs, gnu, bsd, sfv or hashdeep (defaults to guessing from the output file name)"
+        )]
+        format: Option<ChecksumSetFormat>,
+        #[clap(
+            long = "allow-mixed-hash-types",
+            help = "Allow checksum sets with different hash types, keeping only the hash types present in both"
+        )]
+        allow_mixed_hash_types: bool,
+        #[clap(
+            long = "backup",
+            help = "Keep the previous version of the output file, if any, with a timestamp suffix"
+        )]
+        backup: bool,
+    },
+
+    #[clap(about = "Keep only the files of a checksum set matching the specified criteria")]
+    Filter {
+        #[clap(help = "Checksum set to filter")]
+        input_path: PathBuf,
+        #[clap(long = "output", short = 'o', help = "Output file path, or - for standard output")]
+        output_path: PathBuf,
+        #[clap(
+            long = "format",
+            short = 'f',
+            help = "Output format: kecs, gnu, bsd, sfv or hashdeep (defaults to guessing from the output file name)"
+        )]
+        format: Option<ChecksumSetFormat>,
+        #[clap(
+            long = "include",
+            help = "Only keep files matching the specified glob pattern (may be repeated)"
+        )]
+        include: Vec<String>,
+        #[clap(
+            long = "exclude",
+            help = "Remove files and directories matching the specified glob pattern (may be repeated)"
+        )]
+        exclude: Vec<String>,
+        #[clap(long = "min-size", help = "Only keep files of at least the specified size, in bytes")]
+        min_size: Option<u64>,
+        #[clap(long = "max-size", help = "Only keep files of at most the specified size, in bytes")]
+        max_size: Option<u64>,
+        #[clap(
+            long = "backup",
+            help = "Keep the previous version of the output file, if any, with a timestamp suffix"
+        )]
+        backup: bool,
+    },
+
+    #[clap(about = "Move the root of a checksum set by removing or adding a prefix to its paths")]
+    Reroot {
+        #[clap(help = "Checksum set to reroot")]
+        input_path: PathBuf,
+        #[clap(long = "output", short = 'o', help = "Output file path, or - for standard output")]
+        output_path: PathBuf,
+        #[clap(
+            long = "format",
+            short = 'f',
+            help = "Output format: kecs, gnu, bsd, sfv or hashdeep (defaults to guessing from the output file name)"
+        )]
+        format: Option<ChecksumSetFormat>,
+        #[clap(
+            long = "strip-prefix",
+            default_value = "",
+            help = "Remove the specified directory from the start of every path, removing files outside of it"
+        )]
+        strip_prefix: String,
+        #[clap(
+            long = "add-prefix",
+            default_value = "",
+            help = "Add the specified directory to the start of every path"
+        )]
+        add_prefix: String,
+        #[clap(
+            long = "backup",
+            help = "Keep the previous version of the output file, if any, with a timestamp suffix"
+        )]
+        backup: bool,
     },
 }
 
-fn main() -> Result<(), anyhow::Error> {
-    let opt = Opt::parse();
+fn main() -> ExitCode {
+    let opt = match Opt::try_parse() {
+        Ok(opt) => opt,
+        Err(err) => {
+            let _ = err.print();
+
+            // Help and version output is not an error.
+            return if err.use_stderr() {
+                Status::UsageError.into()
+            } else {
+                Status::Success.into()
+            };
+        }
+    };
 
     // Initialize logging
     initialize_logging();
@@ -63,26 +453,213 @@ fn main() -> Result<(), anyhow::Error> {
 
     let mut ui = FancyUiHandler::new();
 
-    match opt.command {
+    let result = run(opt.command, &mut ui);
+
+    let _ = ui.clear();
+
+    match result {
+        Ok(status) => status.into(),
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+
+            Status::from_error(&err).into()
+        }
+    }
+}
+
+fn run(cmd: Command, ui: &mut FancyUiHandler) -> Result<Status, anyhow::Error> {
+    match cmd {
         Command::Generate {
             path,
             root_path,
             output_file,
+            format,
+            hash_types,
+            include,
+            exclude,
+            ignore_files,
+            metadata,
+            follow_symlinks,
+            threads,
+            backup,
+            resume,
+            checkpoint_interval,
+        } => command::generate(
+            &path,
+            output_file.as_deref(),
+            root_path.as_deref(),
+            command::GenerateOptions {
+                format,
+                hash_types,
+                include,
+                exclude,
+                ignore_files,
+                metadata,
+                follow_symlinks,
+                threads,
+                backup,
+                resume,
+                checkpoint_interval,
+            },
+            ui,
+        ),
+        Command::Hash { paths, hash_types, tag } => command::hash(&paths, &hash_types, tag),
+        Command::Convert {
+            input_path,
+            output_path,
+            format,
             hash_type,
-        } => command::generate(&path, output_file.as_deref(), root_path.as_deref(), hash_type, &mut ui)?,
+            backup,
+        } => command::convert(
+            &input_path,
+            &output_path,
+            format,
+            &WriteOptions { hash_type, backup },
+            ui,
+        ),
         Command::Diff {
-            checksums_a_path,
-            checksums_b_path,
-        } => command::diff(&checksums_a_path, &checksums_b_path, &mut ui)?,
+            path_a,
+            path_b,
+            root_path_a,
+            root_path_b,
+            ignore_metadata,
+            threads,
+            output_format,
+        } => command::diff(
+            &path_a,
+            &path_b,
+            command::DiffOptions {
+                root_path_a,
+                root_path_b,
+                ignore_metadata,
+                threads,
+            },
+            output_format,
+            ui,
+        ),
         Command::Verify {
             checksums_path,
             root_path,
-        } => command::verify(&checksums_path, root_path.as_deref(), &mut ui)?,
-    };
-
-    ui.clear()?;
-
-    Ok(())
+            hash_types,
+            hash_additional,
+            ignore_metadata,
+            output_format,
+            threads,
+            checkpoint,
+            resume,
+            checkpoint_interval,
+        } => command::verify(
+            &checksums_path,
+            root_path.as_deref(),
+            command::VerifyOptions {
+                hash_types,
+                hash_additional,
+                ignore_metadata,
+                threads,
+                checkpoint,
+                checkpoint_interval,
+                resume,
+            },
+            output_format,
+            ui,
+        ),
+        Command::Audit {
+            checksums_path,
+            root_path,
+            threads,
+        } => command::audit(&checksums_path, root_path.as_deref(), threads, ui),
+        Command::Info {
+            checksums_path,
+            hash_type,
+            depth,
+        } => command::info(&checksums_path, hash_type, depth, ui),
+        Command::Update {
+            checksums_path,
+            root_path,
+            threads,
+            backup,
+        } => command::update(&checksums_path, root_path.as_deref(), threads, backup, ui),
+        Command::Dupes {
+            checksums_paths,
+            root_paths,
+            link,
+        } => command::dupes(&checksums_paths, &root_paths, link, ui),
+        Command::Merge {
+            input_paths,
+            output_path,
+            format,
+            on_conflict,
+            allow_mixed_hash_types,
+            backup,
+        } => command::merge(
+            &input_paths,
+            &output_path,
+            format,
+            &MergeOptions {
+                on_conflict,
+                allow_mixed_hash_types,
+            },
+            backup,
+            ui,
+        ),
+        Command::Subtract {
+            path_a,
+            path_b,
+            output_path,
+            format,
+            allow_mixed_hash_types,
+            backup,
+        } => command::subtract(
+            &path_a,
+            &path_b,
+            &output_path,
+            format,
+            allow_mixed_hash_types,
+            backup,
+            ui,
+        ),
+        Command::Filter {
+            input_path,
+            output_path,
+            format,
+            include,
+            exclude,
+            min_size,
+            max_size,
+            backup,
+        } => command::filter(
+            &input_path,
+            &output_path,
+            format,
+            &FileSelection {
+                filter: FileFilter {
+                    include,
+                    exclude,
+                    ..Default::default()
+                },
+                min_size,
+                max_size,
+            },
+            backup,
+            ui,
+        ),
+        Command::Reroot {
+            input_path,
+            output_path,
+            format,
+            strip_prefix,
+            add_prefix,
+            backup,
+        } => command::reroot(
+            &input_path,
+            &output_path,
+            format,
+            &strip_prefix,
+            &add_prefix,
+            backup,
+            ui,
+        ),
+    }
 }
 
 fn initialize_logging() {
@@ -0,0 +1,58 @@
+use std::process::ExitCode;
+
+use kecs::checksum_set::{AuditReport, ChecksumSetDiff, HashError};
+
+/// Outcome of a command, determining the process exit code.
+///
+/// * 0 - Success. No differences were found.
+/// * 1 - Differences were found.
+/// * 2 - Some files could not be read, or another I/O error occurred.
+/// * 3 - Invalid usage, or a checksum set could not be parsed or used.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Status {
+    Success = 0,
+    Differences = 1,
+    IoError = 2,
+    UsageError = 3,
+}
+
+impl Status {
+    pub fn from_diff(diff: &ChecksumSetDiff) -> Self {
+        if !diff.unreadable_files.is_empty() {
+            Self::IoError
+        } else if diff.is_different() {
+            Self::Differences
+        } else {
+            Self::Success
+        }
+    }
+
+    pub fn from_audit(report: &AuditReport) -> Self {
+        if !report.unreadable_files.is_empty() {
+            Self::IoError
+        } else if !report.passed() {
+            Self::Differences
+        } else {
+            Self::Success
+        }
+    }
+
+    /// Determine the status of a failed command from its error.
+    ///
+    /// Errors caused by I/O are reported as I/O errors, and anything else as usage errors.
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        let is_io_error = err.chain().any(|e| e.is::<std::io::Error>() || e.is::<HashError>());
+
+        if is_io_error {
+            Self::IoError
+        } else {
+            Self::UsageError
+        }
+    }
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use kecs::ui::UiHandler;
@@ -24,7 +24,7 @@ pub struct FancyUiHandler {
     scan_pb: Option<ProgressBar>,
     prepare_pb: Option<ProgressBar>,
     overall_pb: Option<ProgressBar>,
-    file_pb: Option<ProgressBar>,
+    file_pbs: HashMap<usize, ProgressBar>,
 }
 
 impl FancyUiHandler {
@@ -39,7 +39,7 @@ impl FancyUiHandler {
             scan_pb: None,
             prepare_pb: None,
             overall_pb: None,
-            file_pb: None,
+            file_pbs: HashMap::new(),
         }
     }
 
@@ -175,7 +175,7 @@ impl UiHandler for FancyUiHandler {
         }
     }
 
-    fn begin_file(&mut self, filename: &str, size: u64) {
+    fn begin_file(&mut self, id: usize, filename: &str, size: u64) {
         let pb = ProgressBar::new(size)
             .with_style(
                 ProgressStyle::default_bar()
@@ -188,11 +188,11 @@ impl UiHandler for FancyUiHandler {
 
         let pb = self.multi_progress.add(pb);
 
-        self.file_pb = Some(pb);
+        self.file_pbs.insert(id, pb);
     }
 
-    fn file_progress(&mut self, bytes: u64) {
-        if let Some(pb) = self.file_pb.as_ref() {
+    fn file_progress(&mut self, id: usize, bytes: u64) {
+        if let Some(pb) = self.file_pbs.get(&id) {
             pb.inc(bytes);
         }
 
@@ -201,8 +201,8 @@ impl UiHandler for FancyUiHandler {
         }
     }
 
-    fn end_file(&mut self) {
-        if let Some(pb) = self.file_pb.take() {
+    fn end_file(&mut self, id: usize) {
+        if let Some(pb) = self.file_pbs.remove(&id) {
             pb.finish_and_clear();
         }
     }
@@ -0,0 +1,243 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tracing::warn;
+
+use crate::{ui::UiHandler, Error};
+
+use super::{
+    diff,
+    pool::{self, HashJob},
+    scan, ChecksumSet, HashType,
+};
+
+#[derive(Debug)]
+pub struct AuditOptions {
+    /// Number of worker threads used to hash files.
+    pub threads: usize,
+}
+
+impl Default for AuditOptions {
+    fn default() -> Self {
+        Self {
+            threads: pool::default_thread_count(),
+        }
+    }
+}
+
+/// Result of auditing a directory tree against a checksum set.
+///
+/// Unlike verifying, every file found is hashed and matched against all
+/// known files regardless of its path, in the same way as `hashdeep -a`.
+#[derive(Debug, Default)]
+pub struct AuditReport {
+    /// Number of files examined.
+    pub examined_count: usize,
+    /// Number of files in the checksum set.
+    pub known_count: usize,
+    /// Files matching the known file at the same path.
+    pub matched_files: BTreeSet<String>,
+    /// Files matching a known file at a different path, mapped to that path.
+    pub moved_files: BTreeMap<String, String>,
+    /// Files where some, but not all, hashes match a known file, mapped to that file.
+    pub partially_matched_files: BTreeMap<String, String>,
+    /// Files not matching any known file.
+    pub new_files: BTreeSet<String>,
+    /// Known files that did not match any file examined.
+    pub missing_files: BTreeSet<String>,
+    /// Files that could not be read.
+    pub unreadable_files: BTreeSet<String>,
+}
+
+impl ChecksumSet {
+    /// Audit the root path against the checksum set.
+    pub fn audit(
+        &self,
+        root_path: &Path,
+        options: &AuditOptions,
+        ui: &mut dyn UiHandler,
+    ) -> Result<AuditReport, Error> {
+        let filter = Arc::new(self.filter.compile()?);
+
+        ui.begin_scan();
+
+        let found_files = scan::scan_relative(root_path, &self.scan_paths(), &filter, self.follow_symlinks).files;
+
+        ui.end_scan();
+
+        ui.begin_prepare();
+
+        let found_files: Vec<(String, PathBuf, u64)> = found_files
+            .into_iter()
+            .map(|(rel_path, file_path)| {
+                let size = std::fs::metadata(&file_path).map(|m| m.len()).unwrap_or(0);
+
+                (rel_path, file_path, size)
+            })
+            .collect();
+
+        let jobs: Vec<HashJob> = found_files
+            .iter()
+            .map(|(_, path, size)| HashJob { path, size: *size })
+            .collect();
+
+        let total_size: u64 = jobs.iter().map(|job| job.size).sum();
+
+        // Index known files by each of their hashes, to find matches regardless of path.
+        let mut known_hashes: HashMap<(HashType, &str), Vec<&String>> = HashMap::new();
+
+        for (path, fi) in self.files.iter() {
+            for (hash_type, hash) in fi.hashes.iter() {
+                known_hashes.entry((*hash_type, hash.as_str())).or_default().push(path);
+            }
+        }
+
+        ui.end_prepare();
+
+        ui.begin_verify(jobs.len() as u32, total_size);
+
+        let results = pool::hash_files(&self.hash_types, &jobs, options.threads, ui);
+
+        ui.end_verify();
+
+        let mut report = AuditReport {
+            examined_count: found_files.len(),
+            known_count: self.files.len(),
+            ..Default::default()
+        };
+
+        let mut used_files: BTreeSet<&String> = BTreeSet::new();
+
+        for ((rel_path, _, size), result) in found_files.into_iter().zip(results) {
+            let hashes = match result {
+                Ok(hashes) => hashes,
+                Err(err) => {
+                    warn!("{err}");
+                    report.unreadable_files.insert(rel_path);
+                    continue;
+                }
+            };
+
+            let is_match = |path: &String| {
+                let fi = &self.files[path];
+
+                fi.size.is_none_or(|s| s == size)
+                    && diff::compare_hashes(&fi.hashes, &hashes, &self.hash_types).is_none()
+            };
+
+            if let Some((known_path, _)) = self.files.get_key_value(&rel_path).filter(|(p, _)| is_match(p)) {
+                used_files.insert(known_path);
+                report.matched_files.insert(rel_path);
+                continue;
+            }
+
+            // Files matching on any one hash type are candidates for either a full or a partial match.
+            let candidates: BTreeSet<&String> = hashes
+                .iter()
+                .filter_map(|(hash_type, hash)| known_hashes.get(&(*hash_type, hash.as_str())))
+                .flatten()
+                .copied()
+                .collect();
+
+            if let Some(known_path) = candidates.iter().find(|p| is_match(p)) {
+                used_files.insert(known_path);
+                report.moved_files.insert(rel_path, known_path.to_string());
+            } else if let Some(known_path) = candidates.first() {
+                report.partially_matched_files.insert(rel_path, known_path.to_string());
+            } else {
+                report.new_files.insert(rel_path);
+            }
+        }
+
+        report.missing_files = self.files.keys().filter(|p| !used_files.contains(p)).cloned().collect();
+
+        Ok(report)
+    }
+}
+
+impl AuditReport {
+    /// Returns true if every file examined matched a known file, and every known file was found.
+    pub fn passed(&self) -> bool {
+        self.partially_matched_files.is_empty()
+            && self.new_files.is_empty()
+            && self.missing_files.is_empty()
+            && self.unreadable_files.is_empty()
+    }
+
+    pub fn print(&self) {
+        println!();
+
+        if !self.moved_files.is_empty() {
+            println!("-- MOVED FILES --");
+
+            for (path, known_path) in self.moved_files.iter() {
+                println!("{} -> {}", known_path, path);
+            }
+
+            println!();
+        }
+
+        if !self.partially_matched_files.is_empty() {
+            println!("-- PARTIALLY MATCHED FILES --");
+
+            for (path, known_path) in self.partially_matched_files.iter() {
+                println!("{} (partially matches {})", path, known_path);
+            }
+
+            println!();
+        }
+
+        if !self.new_files.is_empty() {
+            println!("-- NEW FILES --");
+
+            for p in self.new_files.iter() {
+                println!("{}", p);
+            }
+
+            println!();
+        }
+
+        if !self.missing_files.is_empty() {
+            println!("-- MISSING FILES --");
+
+            for p in self.missing_files.iter() {
+                println!("{}", p);
+            }
+
+            println!();
+        }
+
+        if !self.unreadable_files.is_empty() {
+            println!("-- UNREADABLE FILES --");
+
+            for p in self.unreadable_files.iter() {
+                println!("{}", p);
+            }
+
+            println!();
+        }
+
+        println!("{} files examined.", self.examined_count);
+        println!("{} known files.", self.known_count);
+        println!("{} files matched.", self.matched_files.len());
+        println!("{} files moved.", self.moved_files.len());
+        println!("{} files partially matched.", self.partially_matched_files.len());
+        println!("{} new files.", self.new_files.len());
+        println!("{} known files missing.", self.missing_files.len());
+
+        if !self.unreadable_files.is_empty() {
+            println!("{} unreadable files.", self.unreadable_files.len());
+        }
+
+        println!();
+
+        if self.passed() {
+            println!("Audit passed.");
+        } else {
+            println!("Audit failed.");
+        }
+    }
+}
@@ -1,54 +1,144 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use tracing::warn;
 
-use crate::{ui::UiHandler, util};
+use crate::{ui::UiHandler, util, Error};
 
-use super::{ChecksumSet, FileInfo, HashType};
+use super::{
+    checkpoint::{Checkpoint, CheckpointOperation},
+    filter::FilterMatcher,
+    pool::{self, HashJob},
+    scan, CheckpointOptions, ChecksumSet, Entry, FileFilter, FileInfo, HashError, HashType, MetadataKind,
+};
 
 #[derive(Debug)]
 struct BuilderFileInfo {
     pub path: PathBuf,
     pub size: u64,
+    pub mtime: Option<i64>,
 }
 
 #[derive(Debug)]
 pub struct ChecksumSetBuilder {
-    hash_type: HashType,
+    hash_types: Vec<HashType>,
     root_path: PathBuf,
+    threads: usize,
+    scan_paths: Vec<String>,
+    filter: FileFilter,
+    filter_matcher: Arc<FilterMatcher>,
+    metadata: BTreeSet<MetadataKind>,
+    follow_symlinks: bool,
     files: Vec<BuilderFileInfo>,
+    entries: BTreeMap<String, Entry>,
+    checkpoint: Option<CheckpointOptions>,
 }
 
 impl ChecksumSetBuilder {
-    pub fn new(hash_type: HashType, root_path: &Path) -> Self {
+    pub fn new(hash_types: &[HashType], root_path: &Path) -> Self {
+        let mut unique_hash_types: Vec<HashType> = Vec::with_capacity(hash_types.len());
+
+        for hash_type in hash_types {
+            if !unique_hash_types.contains(hash_type) {
+                unique_hash_types.push(*hash_type);
+            }
+        }
+
         Self {
-            hash_type,
+            hash_types: unique_hash_types,
             root_path: util::normalize_path(root_path),
+            threads: pool::default_thread_count(),
+            scan_paths: Vec::new(),
+            filter: FileFilter::default(),
+            filter_matcher: Default::default(),
+            metadata: BTreeSet::new(),
+            follow_symlinks: false,
             files: Vec::new(),
+            entries: BTreeMap::new(),
+            checkpoint: None,
         }
     }
 
+    /// Set the number of worker threads used to hash files.
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads.max(1);
+
+        self
+    }
+
+    /// Set the filter used to determine which files to include when adding paths.
+    ///
+    /// This must be set before adding any paths in order to apply to them.
+    pub fn filter(&mut self, filter: FileFilter) -> Result<&mut Self, Error> {
+        self.filter_matcher = Arc::new(filter.compile()?);
+        self.filter = filter;
+
+        Ok(self)
+    }
+
+    /// Set the kinds of metadata to capture for each file, in addition to size and hashes.
+    pub fn metadata(&mut self, kinds: &[MetadataKind]) -> &mut Self {
+        self.metadata = kinds.iter().copied().collect();
+
+        self
+    }
+
+    /// Set whether to follow symlinks, hashing the files they point to,
+    /// rather than recording them and their targets.
+    ///
+    /// This must be set before adding any paths in order to apply to them.
+    pub fn follow_symlinks(&mut self, follow_symlinks: bool) -> &mut Self {
+        self.follow_symlinks = follow_symlinks;
+
+        self
+    }
+
+    /// Periodically save the progress of building to a checkpoint file,
+    /// optionally resuming from an existing one.
+    pub fn checkpoint(&mut self, options: CheckpointOptions) -> &mut Self {
+        self.checkpoint = Some(options);
+
+        self
+    }
+
     pub fn add_file<P: AsRef<Path>>(&mut self, path: P) {
         let path = util::normalize_path(path);
-        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let metadata = std::fs::metadata(&path).ok();
+        let size = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
+        let mtime = metadata.as_ref().and_then(util::modified_nanos);
 
-        self.files.push(BuilderFileInfo { path, size });
+        self.files.push(BuilderFileInfo { path, size, mtime });
     }
 
     pub fn add_path<P: AsRef<Path>>(&mut self, path: P, ui: &mut dyn UiHandler) -> &mut Self {
         ui.begin_scan();
 
-        let entries = walkdir::WalkDir::new(path).into_iter().filter_map(|entry| entry.ok());
+        let path = util::normalize_path(path);
+
+        // Remember which paths were scanned, so that verification can
+        // look for files added to them later.
+        if let Ok(rel_path) = path.strip_prefix(&self.root_path) {
+            let rel_path = util::unixify_path(rel_path);
 
-        for entry in entries {
-            if !entry.file_type().is_file() {
-                continue;
+            if !self.scan_paths.contains(&rel_path) {
+                self.scan_paths.push(rel_path);
             }
+        }
 
-            self.add_file(entry.path());
+        for (entry_path, entry) in
+            scan::scan_entries(&path, &self.root_path, &self.filter_matcher, self.follow_symlinks)
+        {
+            match entry {
+                Some(entry) => {
+                    if let Ok(rel_path) = entry_path.strip_prefix(&self.root_path) {
+                        self.entries.insert(util::unixify_path(rel_path), entry);
+                    }
+                }
+                None => self.add_file(entry_path),
+            }
         }
 
         ui.end_scan();
@@ -56,11 +146,12 @@ impl ChecksumSetBuilder {
         self
     }
 
-    pub fn build(&self, ui: &mut dyn UiHandler) -> Result<ChecksumSet, anyhow::Error> {
-        let hash_type = self.hash_type;
+    pub fn build(&self, ui: &mut dyn UiHandler) -> Result<ChecksumSet, Error> {
+        let hash_types = &self.hash_types;
         let root_path = &self.root_path;
 
         let mut files: BTreeMap<String, FileInfo> = BTreeMap::new();
+        let mut unreadable_files: BTreeSet<String> = BTreeSet::new();
 
         ui.begin_prepare();
 
@@ -74,25 +165,122 @@ impl ChecksumSetBuilder {
 
         ui.begin_generate(self.files.len() as u32, total_size);
 
-        for BuilderFileInfo { path, size } in self.files.iter() {
-            // Make path relative, as we only want to match on the path
-            // relative to the root.
+        // Make paths relative, as we only want to match on the path
+        // relative to the root.
+        let mut rel_paths: Vec<String> = Vec::with_capacity(self.files.len());
+        let mut mtimes: Vec<Option<i64>> = Vec::with_capacity(self.files.len());
+        let mut jobs: Vec<HashJob> = Vec::with_capacity(self.files.len());
+
+        for BuilderFileInfo { path, size, mtime } in self.files.iter() {
             if let Ok(rel_path) = path.strip_prefix(root_path) {
-                let hash = hash_type.hash_file(path, ui)?;
+                rel_paths.push(util::unixify_path(rel_path));
+                mtimes.push(*mtime);
+                jobs.push(HashJob { path, size: *size });
+            } else {
+                warn!("'{}' is outside the root path. Skipping.", path.display());
+            }
+        }
 
-                let rel_path = util::unixify_path(rel_path);
-                let size = *size;
+        let mut checkpoint = match &self.checkpoint {
+            Some(options) => Some(Checkpoint::begin(
+                options,
+                CheckpointOperation::Generate,
+                root_path,
+                hash_types,
+                self.fingerprint(),
+            )?),
+            None => None,
+        };
 
-                files.insert(rel_path, FileInfo { hash, size });
+        // Files already hashed by a resumed run are not hashed again.
+        let mut results: Vec<Option<Result<BTreeMap<HashType, String>, HashError>>> = Vec::with_capacity(jobs.len());
+        let mut hash_indices: Vec<usize> = Vec::new();
+        let mut hash_jobs: Vec<HashJob> = Vec::new();
+
+        for (index, job) in jobs.iter().enumerate() {
+            let hashes = checkpoint
+                .as_ref()
+                .and_then(|cp| cp.hashes(&rel_paths[index], job.size, mtimes[index]));
+
+            if let Some(hashes) = hashes {
+                results.push(Some(Ok(hashes.clone())));
+
+                ui.file_progress(index, job.size);
             } else {
-                warn!("'{}' is outside the root path. Skipping.", path.display());
+                results.push(None);
+                hash_indices.push(index);
+                hash_jobs.push(HashJob {
+                    path: job.path,
+                    size: job.size,
+                });
+            }
+        }
+
+        let hash_results = pool::hash_files_with(hash_types, &hash_jobs, self.threads, ui, |i, result| {
+            if let (Some(checkpoint), Ok(hashes)) = (checkpoint.as_mut(), result) {
+                let index = hash_indices[i];
+
+                checkpoint.record(&rel_paths[index], jobs[index].size, mtimes[index], hashes);
             }
+        });
+
+        for (index, result) in hash_indices.into_iter().zip(hash_results) {
+            results[index] = Some(result);
         }
 
-        let checksums = ChecksumSet { hash_type, files };
+        if let Some(checkpoint) = checkpoint.as_mut() {
+            checkpoint.finish();
+        }
+
+        for (((rel_path, mtime), job), result) in rel_paths.into_iter().zip(mtimes).zip(jobs.iter()).zip(results) {
+            // Every file has either been resumed or hashed at this point.
+            match result.unwrap() {
+                Ok(hashes) => {
+                    let mut fi = FileInfo {
+                        size: Some(job.size),
+                        hashes,
+                        mtime,
+                        ..Default::default()
+                    };
+
+                    if !self.metadata.is_empty() {
+                        if let Ok(metadata) = std::fs::metadata(job.path) {
+                            fi.capture_metadata(job.path, &metadata, &self.metadata);
+                        }
+                    }
+
+                    files.insert(rel_path, fi);
+                }
+                Err(err) => {
+                    warn!("{err}");
+                    unreadable_files.insert(rel_path);
+                }
+            }
+        }
+
+        let checksums = ChecksumSet {
+            hash_types: hash_types.clone(),
+            files,
+            unreadable_files,
+            scan_paths: self.scan_paths.clone(),
+            filter: self.filter.clone(),
+            metadata: self.metadata.clone(),
+            entries: Some(self.entries.clone()),
+            follow_symlinks: self.follow_symlinks,
+        };
 
         ui.end_generate();
 
         Ok(checksums)
     }
+
+    /// Fingerprint of the options determining which files are included and what is captured,
+    /// used to make sure a resumed checkpoint was made by the same run.
+    fn fingerprint(&self) -> String {
+        // Serializing the options cannot fail.
+        let options = serde_json::to_vec(&(&self.scan_paths, &self.filter, &self.metadata, self.follow_symlinks))
+            .unwrap_or_default();
+
+        HashType::Sha256.hash_bytes(&options)
+    }
 }
@@ -0,0 +1,394 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde_derive::{Deserialize, Serialize};
+use strum_macros::Display;
+use tracing::warn;
+
+use crate::{util, Error};
+
+use super::HashType;
+
+/// Version of the checkpoint file format.
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// Where and how often to save the progress of a generate or verify run,
+/// so that it can be resumed if interrupted.
+///
+/// The checkpoint file is kept once the run has finished, and is left to the caller
+/// to remove once the results have been saved.
+#[derive(Clone, Debug)]
+pub struct CheckpointOptions {
+    /// Path of the checkpoint file.
+    pub path: PathBuf,
+    /// Minimum time between saving checkpoints.
+    pub interval: Duration,
+    /// Resume from the checkpoint file if it exists, skipping files already hashed
+    /// by the previous run, provided they have not been modified since.
+    pub resume: bool,
+}
+
+impl CheckpointOptions {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            interval: Duration::from_secs(60),
+            resume: false,
+        }
+    }
+}
+
+/// Operation a checkpoint was made by.
+#[derive(Clone, Copy, Debug, Deserialize, Display, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(super) enum CheckpointOperation {
+    Generate,
+    Verify,
+}
+
+/// File hashed before a checkpoint was saved.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct HashedFile {
+    size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mtime: Option<i64>,
+    hashes: BTreeMap<HashType, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CheckpointFile {
+    version: u32,
+    operation: CheckpointOperation,
+    root_path: PathBuf,
+    hash_types: Vec<HashType>,
+    /// Fingerprint of the checksum set being verified, or of the options used to generate one.
+    fingerprint: String,
+    /// Files hashed so far, keyed by path relative to the root.
+    files: BTreeMap<String, HashedFile>,
+}
+
+/// Progress of a generate or verify run, periodically saved to a checkpoint file.
+pub(super) struct Checkpoint {
+    path: PathBuf,
+    interval: Duration,
+    state: CheckpointFile,
+    last_saved: Instant,
+    saved: bool,
+    save_failed: bool,
+}
+
+impl Checkpoint {
+    /// Begin checkpointing a run, resuming from an existing checkpoint file if requested.
+    ///
+    /// Fails if the checkpoint being resumed was made by a different operation, or for a
+    /// different checksum set, root path or hash types.
+    pub(super) fn begin(
+        options: &CheckpointOptions,
+        operation: CheckpointOperation,
+        root_path: &Path,
+        hash_types: &[HashType],
+        fingerprint: String,
+    ) -> Result<Self, Error> {
+        let mut state = CheckpointFile {
+            version: CHECKPOINT_VERSION,
+            operation,
+            root_path: util::normalize_path(root_path),
+            hash_types: hash_types.to_vec(),
+            fingerprint,
+            files: BTreeMap::new(),
+        };
+
+        match load_checkpoint(&options.path)? {
+            Some(previous) if options.resume => {
+                state
+                    .validate_resume(&previous)
+                    .map_err(|reason| Error::CheckpointMismatch {
+                        path: options.path.clone(),
+                        reason,
+                    })?;
+
+                state.files = previous.files;
+            }
+            Some(_) => {
+                warn!(
+                    "A checkpoint from a previous run exists, and will be overwritten: {}",
+                    options.path.display()
+                );
+            }
+            None if options.resume => {
+                warn!(
+                    "No checkpoint found to resume from, starting from the beginning: {}",
+                    options.path.display()
+                );
+            }
+            None => {}
+        }
+
+        Ok(Self {
+            path: options.path.clone(),
+            interval: options.interval,
+            state,
+            last_saved: Instant::now(),
+            saved: false,
+            save_failed: false,
+        })
+    }
+
+    /// Get the hashes of a file hashed by a previous run, if its size and modification time are unchanged.
+    pub(super) fn hashes(&self, path: &str, size: u64, mtime: Option<i64>) -> Option<&BTreeMap<HashType, String>> {
+        self.state
+            .files
+            .get(path)
+            .filter(|hf| hf.size == size && hf.mtime.is_some() && hf.mtime == mtime)
+            .map(|hf| &hf.hashes)
+    }
+
+    /// Record a hashed file, saving the checkpoint if enough time has passed since it was last saved.
+    pub(super) fn record(&mut self, path: &str, size: u64, mtime: Option<i64>, hashes: &BTreeMap<HashType, String>) {
+        self.state.files.insert(
+            path.to_owned(),
+            HashedFile {
+                size,
+                mtime,
+                hashes: hashes.clone(),
+            },
+        );
+
+        if self.last_saved.elapsed() >= self.interval {
+            self.save();
+        }
+    }
+
+    /// Save the final state of the run, unless it was short enough for no checkpoint to have been saved.
+    pub(super) fn finish(&mut self) {
+        if self.saved || self.last_saved.elapsed() >= self.interval {
+            self.save();
+        }
+    }
+
+    /// Save the checkpoint file.
+    ///
+    /// Failing to save is not fatal, as it only prevents resuming. A warning is shown the first time it happens.
+    fn save(&mut self) {
+        let result = util::write_file_atomic(&self.path, false, |writer| {
+            serde_json::to_writer(writer, &self.state).map_err(io::Error::from)
+        });
+
+        if let Err(err) = result {
+            if !self.save_failed {
+                warn!("Saving checkpoint file failed: {}: {err}", self.path.display());
+            }
+
+            self.save_failed = true;
+        }
+
+        self.last_saved = Instant::now();
+        self.saved = true;
+    }
+}
+
+impl CheckpointFile {
+    /// Check that a previous checkpoint was made by the same run as this one,
+    /// returning the reason if it was not.
+    fn validate_resume(&self, previous: &CheckpointFile) -> Result<(), String> {
+        if previous.version != CHECKPOINT_VERSION {
+            return Err(format!(
+                "Unsupported checkpoint version: {}. Expected {CHECKPOINT_VERSION}.",
+                previous.version
+            ));
+        }
+
+        if previous.operation != self.operation {
+            return Err(format!(
+                "Checkpoint was made by {}, and cannot be used to resume {}.",
+                previous.operation, self.operation
+            ));
+        }
+
+        if previous.root_path != self.root_path {
+            return Err(format!(
+                "Checkpoint was made with a different root path: {}",
+                previous.root_path.display()
+            ));
+        }
+
+        if previous.hash_types != self.hash_types {
+            let names: Vec<String> = previous.hash_types.iter().map(|ht| ht.to_string()).collect();
+
+            return Err(format!(
+                "Checkpoint was made with different hash types: {}",
+                names.join(", ")
+            ));
+        }
+
+        if previous.fingerprint != self.fingerprint {
+            return Err(match self.operation {
+                CheckpointOperation::Generate => "Checkpoint was made with different options.",
+                CheckpointOperation::Verify => "Checkpoint was made for a different checksum set.",
+            }
+            .to_owned());
+        }
+
+        Ok(())
+    }
+}
+
+fn load_checkpoint(path: &Path) -> Result<Option<CheckpointFile>, Error> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => {
+            return Err(Error::Read {
+                path: path.to_path_buf(),
+                source,
+            })
+        }
+    };
+
+    serde_json::from_reader(BufReader::new(file))
+        .map(Some)
+        .map_err(|source| Error::Deserialize {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        checksum_set::VerifyOptions,
+        test_util::{checksum_set, NullUi, TempDir},
+    };
+
+    use super::*;
+
+    fn options(dir: &TempDir, interval: u64, resume: bool) -> CheckpointOptions {
+        CheckpointOptions {
+            interval: Duration::from_secs(interval),
+            resume,
+            ..CheckpointOptions::new(&dir.path().join("checkpoint"))
+        }
+    }
+
+    fn begin(
+        options: &CheckpointOptions,
+        operation: CheckpointOperation,
+        root_path: &str,
+        fingerprint: &str,
+    ) -> Result<Checkpoint, Error> {
+        Checkpoint::begin(
+            options,
+            operation,
+            Path::new(root_path),
+            &[HashType::Md5],
+            fingerprint.to_owned(),
+        )
+    }
+
+    fn mismatch_reason(result: Result<Checkpoint, Error>) -> String {
+        match result {
+            Err(Error::CheckpointMismatch { reason, .. }) => reason,
+            Err(err) => panic!("Unexpected error: {err}"),
+            Ok(_) => panic!("Resuming unexpectedly succeeded."),
+        }
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let dir = TempDir::new();
+        let hashes = BTreeMap::from([(HashType::Md5, HashType::Md5.hash_bytes(b"a"))]);
+
+        let mut checkpoint = begin(&options(&dir, 0, false), CheckpointOperation::Verify, "/root", "a").unwrap();
+        checkpoint.record("a", 1, Some(10), &hashes);
+        checkpoint.record("b", 1, None, &hashes);
+        checkpoint.finish();
+
+        let checkpoint = begin(&options(&dir, 0, true), CheckpointOperation::Verify, "/root", "a").unwrap();
+
+        assert_eq!(checkpoint.hashes("a", 1, Some(10)), Some(&hashes));
+        assert_eq!(checkpoint.hashes("a", 1, Some(11)), None);
+        assert_eq!(checkpoint.hashes("a", 2, Some(10)), None);
+        assert_eq!(checkpoint.hashes("b", 1, None), None);
+        assert_eq!(checkpoint.hashes("c", 1, Some(10)), None);
+    }
+
+    #[test]
+    fn resume_validation() {
+        let dir = TempDir::new();
+
+        begin(&options(&dir, 0, false), CheckpointOperation::Verify, "/root", "a")
+            .unwrap()
+            .finish();
+
+        let resume = options(&dir, 0, true);
+
+        assert_eq!(
+            mismatch_reason(begin(&resume, CheckpointOperation::Verify, "/root", "b")),
+            "Checkpoint was made for a different checksum set."
+        );
+        assert_eq!(
+            mismatch_reason(begin(&resume, CheckpointOperation::Generate, "/root", "a")),
+            "Checkpoint was made by verify, and cannot be used to resume generate."
+        );
+        assert_eq!(
+            mismatch_reason(begin(&resume, CheckpointOperation::Verify, "/other", "a")),
+            "Checkpoint was made with a different root path: /root"
+        );
+        assert_eq!(
+            mismatch_reason(Checkpoint::begin(
+                &resume,
+                CheckpointOperation::Verify,
+                Path::new("/root"),
+                &[HashType::Sha1],
+                "a".to_owned(),
+            )),
+            "Checkpoint was made with different hash types: md5"
+        );
+
+        // Starting over without resuming overwrites the checkpoint.
+        assert!(begin(&options(&dir, 0, false), CheckpointOperation::Verify, "/root", "b").is_ok());
+    }
+
+    #[test]
+    fn short_runs_are_not_saved() {
+        let dir = TempDir::new();
+        let options = options(&dir, 60, false);
+
+        let mut checkpoint = begin(&options, CheckpointOperation::Generate, "/root", "a").unwrap();
+        checkpoint.record("a", 1, Some(10), &BTreeMap::new());
+        checkpoint.finish();
+
+        assert!(!options.path.exists());
+    }
+
+    #[test]
+    fn verify_resume_with_different_checksum_set() {
+        let dir = TempDir::new();
+        dir.write("root/a", "1");
+
+        let root_path = dir.path().join("root");
+        let verify_options = |resume| VerifyOptions {
+            checkpoint: Some(options(&dir, 0, resume)),
+            ..Default::default()
+        };
+
+        let checksums = checksum_set(&[HashType::Md5], &[("a", "1")]);
+        let other = checksum_set(&[HashType::Md5], &[("a", "2")]);
+
+        checksums
+            .verify(&root_path, &verify_options(false), &mut NullUi)
+            .unwrap();
+
+        assert!(checksums.verify(&root_path, &verify_options(true), &mut NullUi).is_ok());
+        assert!(matches!(
+            other.verify(&root_path, &verify_options(true), &mut NullUi),
+            Err(Error::CheckpointMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,574 @@
+use std::{collections::BTreeMap, path::Path};
+
+use strum_macros::{Display, EnumString};
+use tracing::warn;
+
+use crate::Error;
+
+use super::{diff, filter::FilterMatcher, ChecksumSet, Entry, FileFilter, FileInfo, HashType};
+
+/// How to resolve a path present in both checksum sets being merged, but with different contents.
+#[derive(Copy, Clone, Debug, Default, Display, EnumString, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum MergeConflict {
+    /// Fail the merge.
+    #[default]
+    Fail,
+    /// Keep the file from the first checksum set.
+    First,
+    /// Keep the file from the last checksum set.
+    Last,
+}
+
+#[derive(Debug, Default)]
+pub struct MergeOptions {
+    /// How to resolve paths with different contents in each checksum set.
+    pub on_conflict: MergeConflict,
+    /// Allow merging checksum sets with different hash types.
+    /// Only the hash types present in both are kept.
+    pub allow_mixed_hash_types: bool,
+}
+
+/// Criteria for selecting files from a checksum set.
+#[derive(Debug, Default)]
+pub struct FileSelection {
+    /// Filter patterns that files must match.
+    pub filter: FileFilter,
+    /// Minimum size of files, in bytes.
+    pub min_size: Option<u64>,
+    /// Maximum size of files, in bytes.
+    pub max_size: Option<u64>,
+}
+
+impl ChecksumSet {
+    /// Merge another checksum set into this one.
+    ///
+    /// Paths present in both with the same contents are kept once, while paths whose
+    /// contents differ are resolved as specified by `options.on_conflict`.
+    /// Symlinks, directories and special files are only kept if both checksum sets record them.
+    pub fn merge(mut self, other: ChecksumSet, options: &MergeOptions) -> Result<ChecksumSet, Error> {
+        let hash_types = self.combined_hash_types(&other, options.allow_mixed_hash_types)?;
+
+        let take_other = |path: &str| match options.on_conflict {
+            MergeConflict::Fail => Err(Error::MergeConflict { path: path.to_owned() }),
+            MergeConflict::First => Ok(false),
+            MergeConflict::Last => Ok(true),
+        };
+
+        self.retain_hash_types(&hash_types);
+
+        let mut scan_paths = self.scan_paths();
+
+        for scan_path in other.scan_paths() {
+            if !scan_paths.contains(&scan_path) {
+                scan_paths.push(scan_path);
+            }
+        }
+
+        self.scan_paths = scan_paths;
+
+        if self.filter != other.filter {
+            warn!(
+                "The checksum sets were generated using different filters. The merged checksum set will not have one."
+            );
+
+            self.filter = FileFilter::default();
+        }
+
+        self.metadata = self.metadata.intersection(&other.metadata).copied().collect();
+
+        let mut entries = match (self.entries.take(), other.entries) {
+            (Some(entries), Some(other_entries)) if self.follow_symlinks == other.follow_symlinks => {
+                Some((entries, other_entries))
+            }
+            _ => {
+                self.follow_symlinks &= other.follow_symlinks;
+
+                None
+            }
+        };
+
+        for (path, mut fi) in other.files {
+            fi.hashes.retain(|ht, _| hash_types.contains(ht));
+
+            let is_conflict = match self.files.get(&path) {
+                Some(existing) => !same_contents(existing, &fi, &hash_types),
+                None => entries.as_ref().is_some_and(|(e, _)| e.contains_key(&path)),
+            };
+
+            if is_conflict && !take_other(&path)? {
+                continue;
+            }
+
+            if let Some((entries, _)) = entries.as_mut() {
+                entries.remove(&path);
+            }
+
+            self.unreadable_files.remove(&path);
+            self.files.insert(path, fi);
+        }
+
+        for path in other.unreadable_files {
+            if !self.files.contains_key(&path) {
+                self.unreadable_files.insert(path);
+            }
+        }
+
+        if let Some((mut entries, other_entries)) = entries {
+            for (path, entry) in other_entries {
+                let is_conflict = match entries.get(&path) {
+                    Some(existing) => *existing != entry,
+                    None => self.files.contains_key(&path),
+                };
+
+                if is_conflict && !take_other(&path)? {
+                    continue;
+                }
+
+                self.files.remove(&path);
+                entries.insert(path, entry);
+            }
+
+            self.entries = Some(entries);
+        }
+
+        Ok(self)
+    }
+
+    /// Remove all files also present in another checksum set with the same contents.
+    ///
+    /// Symlinks, directories and special files are only removed if both checksum sets record them.
+    pub fn subtract(mut self, other: &ChecksumSet, allow_mixed_hash_types: bool) -> Result<ChecksumSet, Error> {
+        let hash_types = self.combined_hash_types(other, allow_mixed_hash_types)?;
+
+        self.files.retain(|path, fi| {
+            !other
+                .files
+                .get(path)
+                .is_some_and(|other_fi| same_contents(fi, other_fi, &hash_types))
+        });
+
+        self.unreadable_files
+            .retain(|path| !other.unreadable_files.contains(path));
+
+        if let (Some(entries), Some(other_entries)) = (self.entries.as_mut(), &other.entries) {
+            entries.retain(|path, entry| other_entries.get(path) != Some(entry));
+        }
+
+        Ok(self)
+    }
+
+    /// Keep only files also present in another checksum set with the same contents.
+    ///
+    /// Symlinks, directories and special files are only removed if both checksum sets record them.
+    pub fn intersect(mut self, other: &ChecksumSet, allow_mixed_hash_types: bool) -> Result<ChecksumSet, Error> {
+        let hash_types = self.combined_hash_types(other, allow_mixed_hash_types)?;
+
+        self.files.retain(|path, fi| {
+            other
+                .files
+                .get(path)
+                .is_some_and(|other_fi| same_contents(fi, other_fi, &hash_types))
+        });
+
+        self.unreadable_files
+            .retain(|path| other.unreadable_files.contains(path));
+
+        if let (Some(entries), Some(other_entries)) = (self.entries.as_mut(), &other.entries) {
+            entries.retain(|path, entry| other_entries.get(path) == Some(entry));
+        }
+
+        Ok(self)
+    }
+
+    /// Keep only files matching the specified criteria.
+    ///
+    /// Files of unknown size are removed if a minimum or maximum size is specified.
+    /// Symlinks, directories and special files are only matched against the filter patterns.
+    ///
+    /// The exclude and ignore patterns are added to the checksum set's filter, as are the include
+    /// patterns if it does not already have any, so that files that were removed are not reported
+    /// as additional files when verifying.
+    pub fn filter_files(mut self, selection: &FileSelection) -> Result<ChecksumSet, Error> {
+        let matcher = selection.filter.compile()?;
+        let has_size_limit = selection.min_size.is_some() || selection.max_size.is_some();
+
+        self.files.retain(|path, fi| {
+            let size_matches = match fi.size {
+                Some(size) => {
+                    selection.min_size.is_none_or(|min| size >= min) && selection.max_size.is_none_or(|max| size <= max)
+                }
+                None => !has_size_limit,
+            };
+
+            size_matches && is_selected(&matcher, path, false)
+        });
+
+        self.unreadable_files
+            .retain(|path| !has_size_limit && is_selected(&matcher, path, false));
+
+        if let Some(entries) = self.entries.as_mut() {
+            entries.retain(|path, entry| is_selected(&matcher, path, *entry == Entry::Directory));
+        }
+
+        self.filter.exclude.extend(selection.filter.exclude.iter().cloned());
+        self.filter.ignore.extend(selection.filter.ignore.iter().cloned());
+
+        if self.filter.include.is_empty() {
+            self.filter.include = selection.filter.include.clone();
+        }
+
+        Ok(self)
+    }
+
+    /// Move the root of the checksum set.
+    ///
+    /// `strip_prefix` is removed from the start of every path, moving the root down into that
+    /// directory. Files outside of it are removed. `add_prefix` is then added to the start of
+    /// every path, moving the root up.
+    pub fn reroot(mut self, strip_prefix: &str, add_prefix: &str) -> Result<ChecksumSet, Error> {
+        let strip_prefix = strip_prefix.trim_matches('/');
+        let add_prefix = add_prefix.trim_matches('/');
+
+        let reroot_path = |path: &str| -> Option<String> {
+            let path = if strip_prefix.is_empty() {
+                path
+            } else {
+                path.strip_prefix(strip_prefix)?.strip_prefix('/')?
+            };
+
+            Some(join_prefix(add_prefix, path))
+        };
+
+        let mut scan_paths: Vec<String> = Vec::new();
+        let mut scans_prefix = false;
+
+        for scan_path in self.scan_paths() {
+            let is_ancestor = scan_path.is_empty() || strip_prefix.starts_with(&format!("{scan_path}/"));
+
+            let Some(scan_path) = reroot_path(&scan_path)
+                .or_else(|| (scan_path == strip_prefix || is_ancestor).then(|| add_prefix.to_owned()))
+            else {
+                continue;
+            };
+
+            scans_prefix |= scan_path == add_prefix;
+
+            if !scan_paths.contains(&scan_path) {
+                scan_paths.push(scan_path);
+            }
+        }
+
+        self.scan_paths = scan_paths;
+
+        self.files = self
+            .files
+            .into_iter()
+            .filter_map(|(path, fi)| Some((reroot_path(&path)?, fi)))
+            .collect();
+
+        self.unreadable_files = self
+            .unreadable_files
+            .iter()
+            .filter_map(|path| reroot_path(path))
+            .collect();
+
+        if let Some(entries) = self.entries.take() {
+            let mut entries: BTreeMap<String, Entry> = entries
+                .into_iter()
+                .filter_map(|(path, entry)| Some((reroot_path(&path)?, entry)))
+                .collect();
+
+            // Scanning a directory records the directory itself, unless it is the root.
+            if scans_prefix && !add_prefix.is_empty() {
+                entries.insert(add_prefix.to_owned(), Entry::Directory);
+            }
+
+            self.entries = Some(entries);
+        }
+
+        let filter = &self.filter;
+
+        if filter
+            .include
+            .iter()
+            .chain(filter.exclude.iter())
+            .chain(filter.ignore.iter())
+            .any(|p| p.trim_end_matches('/').contains('/'))
+        {
+            warn!("The checksum set's filter contains patterns relative to the original root, which may no longer match as intended.");
+        }
+
+        Ok(self)
+    }
+
+    /// Determine the hash types to use when combining this checksum set with another.
+    ///
+    /// Unless mixed hash types are allowed, both must have the same hash types.
+    fn combined_hash_types(&self, other: &ChecksumSet, allow_mixed_hash_types: bool) -> Result<Vec<HashType>, Error> {
+        let hash_types = self.common_hash_types(other);
+
+        if hash_types.is_empty() {
+            return Err(Error::NoCommonHashTypes);
+        }
+
+        if !allow_mixed_hash_types
+            && (hash_types.len() != self.hash_types.len() || hash_types.len() != other.hash_types.len())
+        {
+            return Err(Error::MixedHashTypes {
+                a: self.hash_types.clone(),
+                b: other.hash_types.clone(),
+            });
+        }
+
+        Ok(hash_types)
+    }
+
+    /// Remove all hash types other than the specified ones.
+    fn retain_hash_types(&mut self, hash_types: &[HashType]) {
+        self.hash_types.retain(|ht| hash_types.contains(ht));
+
+        for fi in self.files.values_mut() {
+            fi.hashes.retain(|ht, _| hash_types.contains(ht));
+        }
+    }
+}
+
+/// Determine whether two files have the same contents, comparing their sizes if known,
+/// and the specified hash types.
+fn same_contents(a: &FileInfo, b: &FileInfo, hash_types: &[HashType]) -> bool {
+    let same_size = match (a.size, b.size) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    };
+
+    same_size && diff::compare_hashes(&a.hashes, &b.hashes, hash_types).is_none()
+}
+
+/// Determine whether a path, and every directory containing it, is selected by a filter.
+fn is_selected(matcher: &FilterMatcher, path: &str, is_dir: bool) -> bool {
+    let mut ancestors = path.match_indices('/').map(|(i, _)| &path[..i]);
+
+    !ancestors.any(|dir| matcher.is_excluded(Path::new(dir), true))
+        && !matcher.is_excluded(Path::new(path), is_dir)
+        && matcher.is_included(Path::new(path))
+}
+
+fn join_prefix(prefix: &str, path: &str) -> String {
+    match (prefix.is_empty(), path.is_empty()) {
+        (true, _) => path.to_owned(),
+        (false, true) => prefix.to_owned(),
+        (false, false) => format!("{prefix}/{path}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::checksum_set;
+
+    use super::*;
+
+    fn with_entries(mut checksums: ChecksumSet, entries: &[(&str, Entry)]) -> ChecksumSet {
+        checksums.entries = Some(entries.iter().map(|(p, e)| (p.to_string(), e.clone())).collect());
+        checksums
+    }
+
+    fn merge(a: ChecksumSet, b: ChecksumSet, on_conflict: MergeConflict) -> Result<ChecksumSet, Error> {
+        a.merge(
+            b,
+            &MergeOptions {
+                on_conflict,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn paths(checksums: &ChecksumSet) -> Vec<&str> {
+        checksums.files.keys().map(String::as_str).collect()
+    }
+
+    fn symlink(target: &str) -> Entry {
+        Entry::Symlink {
+            target: target.to_owned(),
+        }
+    }
+
+    #[test]
+    fn merge_files() {
+        let a = checksum_set(&[HashType::Md5], &[("a", "1"), ("b", "2")]);
+        let b = checksum_set(&[HashType::Md5], &[("b", "2"), ("c", "3")]);
+
+        let merged = merge(a, b, MergeConflict::Fail).unwrap();
+
+        assert_eq!(paths(&merged), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn merge_conflicting_files() {
+        let a = || checksum_set(&[HashType::Md5], &[("a", "1")]);
+        let b = || checksum_set(&[HashType::Md5], &[("a", "2")]);
+
+        assert!(matches!(
+            merge(a(), b(), MergeConflict::Fail),
+            Err(Error::MergeConflict { path }) if path == "a"
+        ));
+
+        let first = merge(a(), b(), MergeConflict::First).unwrap();
+        let last = merge(a(), b(), MergeConflict::Last).unwrap();
+
+        assert_eq!(first.files["a"].hash(HashType::Md5), a().files["a"].hash(HashType::Md5));
+        assert_eq!(last.files["a"].hash(HashType::Md5), b().files["a"].hash(HashType::Md5));
+    }
+
+    #[test]
+    fn merge_file_conflicting_with_entry() {
+        let a = || with_entries(checksum_set(&[HashType::Md5], &[]), &[("a", symlink("b"))]);
+        let b = || with_entries(checksum_set(&[HashType::Md5], &[("a", "1")]), &[]);
+
+        assert!(matches!(
+            merge(a(), b(), MergeConflict::Fail),
+            Err(Error::MergeConflict { path }) if path == "a"
+        ));
+
+        let first = merge(a(), b(), MergeConflict::First).unwrap();
+
+        assert!(first.files.is_empty());
+        assert_eq!(first.entries.unwrap()["a"], symlink("b"));
+
+        let last = merge(a(), b(), MergeConflict::Last).unwrap();
+
+        assert_eq!(paths(&last), ["a"]);
+        assert!(last.entries.unwrap().is_empty());
+    }
+
+    #[test]
+    fn merge_entry_conflicting_with_file() {
+        let a = || with_entries(checksum_set(&[HashType::Md5], &[("a", "1")]), &[]);
+        let b = || with_entries(checksum_set(&[HashType::Md5], &[]), &[("a", symlink("b"))]);
+
+        assert!(matches!(
+            merge(a(), b(), MergeConflict::Fail),
+            Err(Error::MergeConflict { path }) if path == "a"
+        ));
+
+        let first = merge(a(), b(), MergeConflict::First).unwrap();
+
+        assert_eq!(paths(&first), ["a"]);
+        assert!(first.entries.unwrap().is_empty());
+
+        let last = merge(a(), b(), MergeConflict::Last).unwrap();
+
+        assert!(last.files.is_empty());
+        assert_eq!(last.entries.unwrap()["a"], symlink("b"));
+    }
+
+    #[test]
+    fn merge_conflicting_entries() {
+        let a = || {
+            with_entries(
+                checksum_set(&[HashType::Md5], &[]),
+                &[("a", symlink("b")), ("d", Entry::Directory)],
+            )
+        };
+        let b = || {
+            with_entries(
+                checksum_set(&[HashType::Md5], &[]),
+                &[("a", symlink("c")), ("d", Entry::Directory)],
+            )
+        };
+
+        assert!(matches!(
+            merge(a(), b(), MergeConflict::Fail),
+            Err(Error::MergeConflict { path }) if path == "a"
+        ));
+        assert_eq!(
+            merge(a(), b(), MergeConflict::Last).unwrap().entries.unwrap()["a"],
+            symlink("c")
+        );
+    }
+
+    #[test]
+    fn merge_drops_entries_unless_both_record_them() {
+        let a = with_entries(checksum_set(&[HashType::Md5], &[]), &[("a", symlink("b"))]);
+        let b = checksum_set(&[HashType::Md5], &[("c", "1")]);
+
+        let merged = merge(a, b, MergeConflict::Fail).unwrap();
+
+        assert_eq!(merged.entries, None);
+        assert_eq!(paths(&merged), ["c"]);
+    }
+
+    #[test]
+    fn merge_mixed_hash_types() {
+        let a = || checksum_set(&[HashType::Md5, HashType::Sha1], &[("a", "1")]);
+        let b = || checksum_set(&[HashType::Sha1], &[("b", "2")]);
+
+        assert!(matches!(
+            merge(a(), b(), MergeConflict::Fail),
+            Err(Error::MixedHashTypes { .. })
+        ));
+
+        let merged = a()
+            .merge(
+                b(),
+                &MergeOptions {
+                    allow_mixed_hash_types: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(merged.hash_types, [HashType::Sha1]);
+        assert!(merged.files.values().all(|fi| fi.hashes.len() == 1));
+    }
+
+    #[test]
+    fn subtract_and_intersect() {
+        let a = || checksum_set(&[HashType::Md5], &[("a", "1"), ("b", "2"), ("c", "3")]);
+        let b = checksum_set(&[HashType::Md5], &[("a", "1"), ("b", "x")]);
+
+        assert_eq!(paths(&a().subtract(&b, false).unwrap()), ["b", "c"]);
+        assert_eq!(paths(&a().intersect(&b, false).unwrap()), ["a"]);
+    }
+
+    #[test]
+    fn reroot_strips_prefix() {
+        let mut checksums = checksum_set(&[HashType::Md5], &[("x/a", "1"), ("x/y/b", "2"), ("xy/c", "3")]);
+        checksums.scan_paths = vec!["x/y".to_owned(), "x/a".to_owned(), "xy".to_owned()];
+
+        let rerooted = checksums.reroot("/x/", "").unwrap();
+
+        assert_eq!(paths(&rerooted), ["a", "y/b"]);
+        assert_eq!(rerooted.scan_paths, ["y", "a"]);
+    }
+
+    #[test]
+    fn reroot_scan_path_ancestors() {
+        for scan_path in ["", "x", "x/y"] {
+            let mut checksums = checksum_set(&[HashType::Md5], &[("x/a", "1"), ("x/y/b", "2")]);
+            checksums.scan_paths = vec![scan_path.to_owned()];
+
+            let rerooted = checksums.reroot("x/y", "").unwrap();
+
+            assert_eq!(paths(&rerooted), ["b"]);
+            assert_eq!(rerooted.scan_paths, [""], "{scan_path}");
+        }
+    }
+
+    #[test]
+    fn reroot_adds_prefix() {
+        let checksums = with_entries(
+            checksum_set(&[HashType::Md5], &[("x/a", "1")]),
+            &[("x", Entry::Directory), ("x/l", symlink("a"))],
+        );
+
+        let rerooted = checksums.reroot("x", "p/q").unwrap();
+
+        assert_eq!(paths(&rerooted), ["p/q/a"]);
+        assert_eq!(rerooted.scan_paths, ["p/q"]);
+        assert_eq!(
+            rerooted.entries.unwrap(),
+            BTreeMap::from([("p/q".to_owned(), Entry::Directory), ("p/q/l".to_owned(), symlink("a"))])
+        );
+    }
+}
@@ -1,73 +1,359 @@
-use crate::ui::UiHandler;
+use crate::{ui::UiHandler, util, Error};
 
-use super::ChecksumSet;
-use std::collections::{BTreeMap, BTreeSet};
+use super::{
+    entry, metadata, ChecksumSet, DiffSide, EntryKind, FileInfo, HashType, MetadataKind, Owner, RehashInfo,
+    VerifyOptions,
+};
+use serde_derive::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    mem,
+    path::Path,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Default, Serialize)]
 pub struct ChecksumSetDiff {
+    /// Hash types that were compared.
+    pub hash_types: Vec<HashType>,
     pub additional_files: BTreeSet<String>,
     pub missing_files: BTreeSet<String>,
     pub differing_sizes: BTreeMap<String, (u64, u64)>,
     pub differing_hashes: BTreeMap<String, (String, String)>,
+    pub unreadable_files: BTreeSet<String>,
+    /// Files found at a different path, mapped from their original path to their new one.
+    pub moved_files: BTreeMap<String, String>,
+    /// Files whose modification time differs, in nanoseconds since the Unix epoch.
+    pub differing_mtimes: BTreeMap<String, (i64, i64)>,
+    /// Files whose Unix permission bits differ.
+    pub differing_modes: BTreeMap<String, (u32, u32)>,
+    /// Files whose Unix owner or group differs, as pairs of owner and group IDs.
+    pub differing_owners: BTreeMap<String, (Owner, Owner)>,
+    /// Files whose extended attributes differ.
+    pub differing_xattrs: BTreeSet<String>,
+    /// Symlinks, directories and special files only present in the first checksum set.
+    pub additional_entries: BTreeMap<String, EntryKind>,
+    /// Symlinks, directories and special files only present in the second checksum set.
+    pub missing_entries: BTreeMap<String, EntryKind>,
+    /// Paths whose kind of entry differs, such as a file having been replaced by a symlink.
+    pub differing_kinds: BTreeMap<String, (EntryKind, EntryKind)>,
+    /// Symlinks whose target differs.
+    pub differing_link_targets: BTreeMap<String, (String, String)>,
+    /// Set if the files of one side had to be rehashed to compare them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rehash: Option<RehashInfo>,
 }
 
 impl ChecksumSet {
-    pub fn diff(&self, other: &ChecksumSet, ui: &mut dyn UiHandler) -> Result<ChecksumSetDiff, anyhow::Error> {
-        if other.hash_type != self.hash_type {
-            return Err(anyhow::anyhow!(
-                "Checksum sets have mismatching hash types. Comparison makes no sense."
-            ));
+    /// Compare with another checksum set.
+    ///
+    /// Any metadata captured in both checksum sets is also compared.
+    pub fn diff(&self, other: &ChecksumSet, ui: &mut dyn UiHandler) -> Result<ChecksumSetDiff, Error> {
+        self.diff_ignoring_metadata(other, &BTreeSet::new(), ui)
+    }
+
+    /// Compare with another checksum set, ignoring the specified kinds of metadata.
+    pub(super) fn diff_ignoring_metadata(
+        &self,
+        other: &ChecksumSet,
+        ignore_metadata: &BTreeSet<MetadataKind>,
+        ui: &mut dyn UiHandler,
+    ) -> Result<ChecksumSetDiff, Error> {
+        let hash_types = self.common_hash_types(other);
+
+        if hash_types.is_empty() {
+            return Err(Error::NoCommonHashTypes);
         }
 
         ui.begin_diff();
 
-        let additional_files = self
+        // Files that could not be read on either side cannot be compared.
+        let unreadable_files: BTreeSet<String> =
+            self.unreadable_files.union(&other.unreadable_files).cloned().collect();
+
+        let mut additional_files: BTreeSet<String> = self
             .files
             .keys()
-            .filter(|p| !other.files.contains_key(*p))
+            .filter(|p| !other.files.contains_key(*p) && !unreadable_files.contains(*p))
             .cloned()
             .collect();
 
+        let metadata_kinds: BTreeSet<MetadataKind> = self
+            .metadata
+            .intersection(&other.metadata)
+            .filter(|k| !ignore_metadata.contains(k))
+            .copied()
+            .collect();
+
         let mut missing_files: BTreeSet<String> = BTreeSet::new();
         let mut differing_sizes: BTreeMap<String, (u64, u64)> = BTreeMap::new();
         let mut differing_hashes: BTreeMap<String, (String, String)> = BTreeMap::new();
 
+        // Metadata and entry differences are recorded directly in the resulting diff.
+        let mut metadata_diff = ChecksumSetDiff::default();
+
+        entry::compare_entries(self, other, &mut metadata_diff);
+
         for (path, other_fi) in other.files.iter() {
             if let Some(fi) = self.files.get(path) {
-                if other_fi.size != fi.size {
-                    differing_sizes.insert(path.into(), (fi.size, other_fi.size));
-                } else if other_fi.hash != fi.hash {
-                    differing_hashes.insert(path.into(), (fi.hash.clone(), other_fi.hash.clone()));
+                match (fi.size, other_fi.size) {
+                    (Some(size), Some(other_size)) if size != other_size => {
+                        differing_sizes.insert(path.into(), (size, other_size));
+                    }
+                    _ => {
+                        if let Some(differing) = compare_hashes(&fi.hashes, &other_fi.hashes, &hash_types) {
+                            differing_hashes.insert(path.into(), differing);
+                        }
+                    }
                 }
-            } else {
+
+                metadata::compare_metadata(path, fi, other_fi, &metadata_kinds, &mut metadata_diff);
+            } else if !unreadable_files.contains(path) && !metadata_diff.differing_kinds.contains_key(path) {
                 missing_files.insert(path.into());
             }
         }
 
+        additional_files.retain(|p| !metadata_diff.differing_kinds.contains_key(p));
+
+        let moved_files = find_moved_files(
+            missing_files.iter().map(|p| (p, &other.files[p])),
+            additional_files.iter().map(|p| (p, &self.files[p])),
+            &hash_types,
+        );
+
+        for (from, to) in moved_files.iter() {
+            missing_files.remove(from);
+            additional_files.remove(to);
+        }
+
         ui.end_diff();
 
         Ok(ChecksumSetDiff {
+            hash_types,
             additional_files,
             missing_files,
             differing_sizes,
             differing_hashes,
+            unreadable_files,
+            moved_files,
+            ..metadata_diff
         })
     }
+
+    /// Compare the checksum set with the current contents of a directory, with the
+    /// same result as generating a checksum set for it and comparing using `diff`.
+    ///
+    /// Files are only hashed when necessary. Files whose size differs are reported
+    /// without being hashed, and files not in the checksum set are only hashed if
+    /// they have the same size as a missing file, to detect whether it was moved.
+    ///
+    /// The paths in the checksum set are relative to `root_path`, and all files in it
+    /// are compared, regardless of which paths were scanned to generate the checksum set.
+    pub fn diff_directory(
+        &self,
+        root_path: &Path,
+        options: &VerifyOptions,
+        ui: &mut dyn UiHandler,
+    ) -> Result<ChecksumSetDiff, Error> {
+        let options = VerifyOptions {
+            threads: options.threads,
+            hash_types: options.hash_types.clone(),
+            find_additional_files: true,
+            hash_additional_files: true,
+            ignore_metadata: options.ignore_metadata.clone(),
+            checkpoint: options.checkpoint.clone(),
+        };
+
+        // Scan the entire directory, as generating a checksum set for it would.
+        let mut diff = self.verify_scan_paths(root_path, &[String::new()], &options, ui)?;
+
+        // Verifying reports files only found in the directory as additional,
+        // while diffing against the directory reports them as missing.
+        mem::swap(&mut diff.additional_files, &mut diff.missing_files);
+        mem::swap(&mut diff.additional_entries, &mut diff.missing_entries);
+
+        diff.moved_files = diff.moved_files.into_iter().map(|(from, to)| (to, from)).collect();
+
+        Ok(diff)
+    }
+}
+
+/// Match missing files with additional files having the same size and hashes,
+/// returning a map of original paths to new paths.
+///
+/// Each file is matched at most once. If several additional files are identical to
+/// a missing file, one with the same file name is preferred, and the rest are left
+/// as additional files. Likewise, if several missing files are identical, only as
+/// many of them as there are identical additional files are considered moved.
+fn find_moved_files<'a>(
+    missing_files: impl Iterator<Item = (&'a String, &'a FileInfo)>,
+    additional_files: impl Iterator<Item = (&'a String, &'a FileInfo)>,
+    hash_types: &[HashType],
+) -> BTreeMap<String, String> {
+    let primary_hash_type = hash_types[0];
+
+    let mut candidates: HashMap<&str, Vec<(&String, &FileInfo)>> = HashMap::new();
+
+    for (path, fi) in additional_files {
+        if let Some(hash) = fi.hash(primary_hash_type) {
+            candidates.entry(hash).or_default().push((path, fi));
+        }
+    }
+
+    let mut moved_files: BTreeMap<String, String> = BTreeMap::new();
+
+    for (path, fi) in missing_files {
+        let Some(candidates) = fi.hash(primary_hash_type).and_then(|h| candidates.get_mut(h)) else {
+            continue;
+        };
+
+        let is_match = |other_fi: &FileInfo| {
+            let same_size = match (fi.size, other_fi.size) {
+                (Some(size), Some(other_size)) => size == other_size,
+                _ => true,
+            };
+
+            same_size && compare_hashes(&fi.hashes, &other_fi.hashes, hash_types).is_none()
+        };
+
+        let index = candidates
+            .iter()
+            .position(|(p, other_fi)| file_name(p) == file_name(path) && is_match(other_fi))
+            .or_else(|| candidates.iter().position(|(_, other_fi)| is_match(other_fi)));
+
+        if let Some(index) = index {
+            let (new_path, _) = candidates.remove(index);
+
+            moved_files.insert(path.clone(), new_path.clone());
+        }
+    }
+
+    moved_files
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Compare two sets of hashes using the specified hash types,
+/// returning the first pair of hashes that differ.
+///
+/// Hash types missing from either side are not compared.
+pub(super) fn compare_hashes(
+    a: &BTreeMap<HashType, String>,
+    b: &BTreeMap<HashType, String>,
+    hash_types: &[HashType],
+) -> Option<(String, String)> {
+    hash_types.iter().find_map(|ht| match (a.get(ht), b.get(ht)) {
+        (Some(a), Some(b)) if a != b => Some((a.clone(), b.clone())),
+        _ => None,
+    })
 }
 
 impl ChecksumSetDiff {
+    /// Swap the sides of the diff, producing the diff that comparing in the opposite order would.
+    pub fn reversed(mut self) -> Self {
+        mem::swap(&mut self.additional_files, &mut self.missing_files);
+        mem::swap(&mut self.additional_entries, &mut self.missing_entries);
+
+        self.moved_files = self.moved_files.into_iter().map(|(from, to)| (to, from)).collect();
+
+        for (a, b) in self.differing_sizes.values_mut() {
+            mem::swap(a, b);
+        }
+
+        for (a, b) in self.differing_hashes.values_mut() {
+            mem::swap(a, b);
+        }
+
+        for (a, b) in self.differing_mtimes.values_mut() {
+            mem::swap(a, b);
+        }
+
+        for (a, b) in self.differing_modes.values_mut() {
+            mem::swap(a, b);
+        }
+
+        for (a, b) in self.differing_owners.values_mut() {
+            mem::swap(a, b);
+        }
+
+        for (a, b) in self.differing_kinds.values_mut() {
+            mem::swap(a, b);
+        }
+
+        for (a, b) in self.differing_link_targets.values_mut() {
+            mem::swap(a, b);
+        }
+
+        if let Some(rehash) = self.rehash.as_mut() {
+            rehash.side = match rehash.side {
+                DiffSide::A => DiffSide::B,
+                DiffSide::B => DiffSide::A,
+            };
+        }
+
+        self
+    }
+
     pub fn is_different(&self) -> bool {
         !self.additional_files.is_empty()
             || !self.missing_files.is_empty()
             || !self.differing_sizes.is_empty()
             || !self.differing_hashes.is_empty()
+            || !self.unreadable_files.is_empty()
+            || !self.moved_files.is_empty()
+            || !self.differing_mtimes.is_empty()
+            || !self.differing_modes.is_empty()
+            || !self.differing_owners.is_empty()
+            || !self.differing_xattrs.is_empty()
+            || !self.additional_entries.is_empty()
+            || !self.missing_entries.is_empty()
+            || !self.differing_kinds.is_empty()
+            || !self.differing_link_targets.is_empty()
+            || self.rehash.as_ref().is_some_and(|r| !r.changed_files.is_empty())
     }
 
+    /// Notice that the files of one side had to be rehashed to compare them, if they were.
+    pub fn rehash_notice(&self) -> Option<String> {
+        let rehash = self.rehash.as_ref()?;
+        let hash_types: Vec<String> = rehash.hash_types.iter().map(|ht| ht.to_string()).collect();
+
+        Some(format!(
+            "Checksum sets have no hash types in common. Files of {} were rehashed using {}.",
+            match rehash.side {
+                DiffSide::A => "A",
+                DiffSide::B => "B",
+            },
+            hash_types.join(", ")
+        ))
+    }
+
+    /// Print the differences.
+    ///
+    /// The rehash notice is not included, and should be printed separately using [`Self::rehash_notice`],
+    /// as it also applies when there are no differences.
     pub fn print(&self) {
         let mut summary: Vec<String> = Vec::new();
 
         println!();
 
+        if let Some(rehash) = &self.rehash {
+            if !rehash.changed_files.is_empty() {
+                println!("-- CHANGED SINCE CHECKSUM SET WAS GENERATED --");
+
+                for p in rehash.changed_files.iter() {
+                    println!("{}", p);
+                }
+
+                println!();
+
+                summary.push(format!(
+                    "{} files changed since checksum set was generated.",
+                    rehash.changed_files.len()
+                ));
+            }
+        }
+
         if !self.additional_files.is_empty() {
             println!("-- ADDITIONAL FILES --");
 
@@ -92,6 +378,66 @@ impl ChecksumSetDiff {
             summary.push(format!("{} missing files.", self.missing_files.len()));
         }
 
+        if !self.moved_files.is_empty() {
+            println!("-- MOVED FILES --");
+
+            for (from, to) in self.moved_files.iter() {
+                println!("{} -> {}", from, to);
+            }
+
+            println!();
+
+            summary.push(format!("{} moved files.", self.moved_files.len()));
+        }
+
+        if !self.additional_entries.is_empty() {
+            println!("-- ADDITIONAL ENTRIES --");
+
+            for (p, kind) in self.additional_entries.iter() {
+                println!("{} ({})", p, kind);
+            }
+
+            println!();
+
+            summary.push(format!("{} additional entries.", self.additional_entries.len()));
+        }
+
+        if !self.missing_entries.is_empty() {
+            println!("-- MISSING ENTRIES --");
+
+            for (p, kind) in self.missing_entries.iter() {
+                println!("{} ({})", p, kind);
+            }
+
+            println!();
+
+            summary.push(format!("{} missing entries.", self.missing_entries.len()));
+        }
+
+        if !self.differing_kinds.is_empty() {
+            println!("-- DIFFERING KINDS --");
+
+            for (p, (a, b)) in self.differing_kinds.iter() {
+                println!("{} == A: {} / B: {}", p, a, b);
+            }
+
+            println!();
+
+            summary.push(format!("{} differing kinds.", self.differing_kinds.len()));
+        }
+
+        if !self.differing_link_targets.is_empty() {
+            println!("-- DIFFERING LINK TARGETS --");
+
+            for (p, (a, b)) in self.differing_link_targets.iter() {
+                println!("{} == A: {} / B: {}", p, a, b);
+            }
+
+            println!();
+
+            summary.push(format!("{} differing link targets.", self.differing_link_targets.len()));
+        }
+
         if !self.differing_sizes.is_empty() {
             println!("-- DIFFERING SIZES --");
 
@@ -116,8 +462,151 @@ impl ChecksumSetDiff {
             summary.push(format!("{} differing hashes.", self.differing_hashes.len()));
         }
 
+        if !self.differing_mtimes.is_empty() {
+            println!("-- DIFFERING MODIFICATION TIMES --");
+
+            for (p, (a, b)) in self.differing_mtimes.iter() {
+                println!(
+                    "{} == A: {} / B: {}",
+                    p,
+                    util::format_timestamp_nanos(*a),
+                    util::format_timestamp_nanos(*b)
+                );
+            }
+
+            println!();
+
+            summary.push(format!("{} differing modification times.", self.differing_mtimes.len()));
+        }
+
+        if !self.differing_modes.is_empty() {
+            println!("-- DIFFERING MODES --");
+
+            for (p, (a, b)) in self.differing_modes.iter() {
+                println!("{} == A: {:04o} / B: {:04o}", p, a, b);
+            }
+
+            println!();
+
+            summary.push(format!("{} differing modes.", self.differing_modes.len()));
+        }
+
+        if !self.differing_owners.is_empty() {
+            println!("-- DIFFERING OWNERS --");
+
+            for (p, ((a_uid, a_gid), (b_uid, b_gid))) in self.differing_owners.iter() {
+                println!("{} == A: {}:{} / B: {}:{}", p, a_uid, a_gid, b_uid, b_gid);
+            }
+
+            println!();
+
+            summary.push(format!("{} differing owners.", self.differing_owners.len()));
+        }
+
+        if !self.differing_xattrs.is_empty() {
+            println!("-- DIFFERING EXTENDED ATTRIBUTES --");
+
+            for p in self.differing_xattrs.iter() {
+                println!("{}", p);
+            }
+
+            println!();
+
+            summary.push(format!(
+                "{} differing extended attributes.",
+                self.differing_xattrs.len()
+            ));
+        }
+
+        if !self.unreadable_files.is_empty() {
+            println!("-- UNREADABLE FILES --");
+
+            for p in self.unreadable_files.iter() {
+                println!("{}", p);
+            }
+
+            println!();
+
+            summary.push(format!("{} unreadable files.", self.unreadable_files.len()));
+        }
+
         for line in summary.iter() {
             println!("{}", line);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{checksum_set, NullUi, TempDir};
+
+    use super::*;
+
+    fn diff_sets(new: &[(&str, &str)], old: &[(&str, &str)]) -> ChecksumSetDiff {
+        checksum_set(&[HashType::Md5], new)
+            .diff(&checksum_set(&[HashType::Md5], old), &mut NullUi)
+            .unwrap()
+    }
+
+    fn moved(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect()
+    }
+
+    #[test]
+    fn moved_file() {
+        let diff = diff_sets(&[("b/x", "1"), ("c", "2")], &[("a/x", "1"), ("c", "2")]);
+
+        assert_eq!(diff.moved_files, moved(&[("a/x", "b/x")]));
+        assert!(diff.additional_files.is_empty());
+        assert!(diff.missing_files.is_empty());
+        assert!(diff.is_different());
+    }
+
+    #[test]
+    fn moved_duplicates_prefer_same_file_name() {
+        let diff = diff_sets(&[("b/y", "1"), ("b/x", "1")], &[("a/x", "1"), ("a/y", "1")]);
+
+        assert_eq!(diff.moved_files, moved(&[("a/x", "b/x"), ("a/y", "b/y")]));
+    }
+
+    #[test]
+    fn moved_duplicates_match_each_file_once() {
+        let diff = diff_sets(&[("b/z", "1")], &[("a/x", "1"), ("a/y", "1")]);
+
+        assert_eq!(diff.moved_files, moved(&[("a/x", "b/z")]));
+        assert_eq!(diff.missing_files, BTreeSet::from(["a/y".to_owned()]));
+        assert!(diff.additional_files.is_empty());
+
+        let diff = diff_sets(&[("b/x", "1"), ("b/y", "1")], &[("a/z", "1")]);
+
+        assert_eq!(diff.moved_files.len(), 1);
+        assert_eq!(diff.additional_files.len(), 1);
+        assert!(diff.missing_files.is_empty());
+    }
+
+    #[test]
+    fn different_contents_are_not_moved() {
+        let diff = diff_sets(&[("b/x", "2")], &[("a/x", "1")]);
+
+        assert!(diff.moved_files.is_empty());
+        assert_eq!(diff.additional_files, BTreeSet::from(["b/x".to_owned()]));
+        assert_eq!(diff.missing_files, BTreeSet::from(["a/x".to_owned()]));
+    }
+
+    #[test]
+    fn moved_file_in_directory() {
+        let dir = TempDir::new();
+        dir.write("b/x", "1");
+        dir.write("c", "22");
+
+        let checksums = checksum_set(&[HashType::Md5], &[("a/x", "1"), ("c", "22")]);
+        let diff = checksums
+            .diff_directory(dir.path(), &VerifyOptions::default(), &mut NullUi)
+            .unwrap();
+
+        // The directory is the other side of the diff, so the file was moved from its path there.
+        assert_eq!(diff.moved_files, moved(&[("b/x", "a/x")]));
+        assert!(diff.additional_files.is_empty());
+        assert!(diff.missing_files.is_empty());
+    }
+}
@@ -0,0 +1,504 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use strum_macros::{Display, EnumString};
+
+use crate::Error;
+
+use super::{ChecksumSet, HashType};
+
+const BUFFER_SIZE: usize = 524288;
+
+/// Method used to replace duplicate files.
+#[derive(Copy, Clone, Debug, Display, EnumString, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum LinkMethod {
+    /// Replace duplicates with hardlinks to the original file.
+    Hardlink,
+    /// Replace duplicates with copy-on-write clones of the original file,
+    /// on filesystems supporting it.
+    Reflink,
+}
+
+/// File in a group of duplicates.
+#[derive(Debug)]
+pub struct DuplicateFile {
+    /// Index of the checksum set containing the file.
+    pub checksum_set: usize,
+    /// Path of the file, relative to its checksum set's root.
+    pub path: String,
+    /// Whether the file is already a hardlink to the file that would be kept when linking.
+    /// Only known after calling [`Duplicates::find_linked_files`].
+    pub linked: bool,
+}
+
+/// Group of files with identical sizes and hashes.
+#[derive(Debug)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub hash: String,
+    /// Files in the group, in order of checksum set and path.
+    pub files: Vec<DuplicateFile>,
+}
+
+/// Duplicate files found in one or more checksum sets.
+#[derive(Debug)]
+pub struct Duplicates {
+    /// Hash type used to identify duplicates.
+    pub hash_type: HashType,
+    /// Groups of duplicates, largest wasted space first.
+    pub groups: Vec<DuplicateGroup>,
+}
+
+/// Result of replacing duplicate files with links.
+#[derive(Debug, Default)]
+pub struct LinkReport {
+    /// Files that were replaced with a link.
+    pub linked_files: Vec<PathBuf>,
+    /// Space reclaimed by replacing files, in bytes.
+    pub reclaimed_space: u64,
+    /// Files that were not replaced, along with the reason.
+    pub skipped_files: BTreeMap<PathBuf, String>,
+    /// Files that could not be replaced, along with the error.
+    pub failed_files: BTreeMap<PathBuf, String>,
+}
+
+impl ChecksumSet {
+    /// Find files with identical sizes and hashes in one or more checksum sets.
+    ///
+    /// Files are compared using the first hash type present in every checksum set,
+    /// in the first checksum set's order of preference. Files of unknown size are ignored.
+    pub fn find_duplicates(checksum_sets: &[ChecksumSet]) -> Result<Duplicates, Error> {
+        let hash_type = checksum_sets
+            .first()
+            .and_then(|first| {
+                first
+                    .hash_types
+                    .iter()
+                    .find(|ht| checksum_sets.iter().all(|cs| cs.hash_types.contains(ht)))
+            })
+            .copied()
+            .ok_or(Error::NoCommonHashTypes)?;
+
+        let mut files_by_content: HashMap<(u64, &str), Vec<DuplicateFile>> = HashMap::new();
+
+        for (index, checksum_set) in checksum_sets.iter().enumerate() {
+            for (path, fi) in checksum_set.files.iter() {
+                let (Some(size), Some(hash)) = (fi.size, fi.hash(hash_type)) else {
+                    continue;
+                };
+
+                files_by_content.entry((size, hash)).or_default().push(DuplicateFile {
+                    checksum_set: index,
+                    path: path.clone(),
+                    linked: false,
+                });
+            }
+        }
+
+        let groups: Vec<DuplicateGroup> = files_by_content
+            .into_iter()
+            .filter(|(_, files)| files.len() > 1)
+            .map(|((size, hash), files)| DuplicateGroup {
+                size,
+                hash: hash.to_owned(),
+                files,
+            })
+            .collect();
+
+        let mut duplicates = Duplicates { hash_type, groups };
+
+        duplicates.sort_groups();
+
+        Ok(duplicates)
+    }
+}
+
+impl DuplicateGroup {
+    /// Space used by all but one of the files, in bytes.
+    /// Files that are already hardlinks to the kept file do not use any additional space.
+    pub fn wasted_space(&self) -> u64 {
+        let unlinked_count = self.files.iter().filter(|f| !f.linked).count() as u64;
+
+        self.size * unlinked_count.saturating_sub(1)
+    }
+
+    /// Index of the file to keep when linking, which is the first file still matching the checksum set.
+    fn original_index(&self, actual_paths: &[PathBuf]) -> Option<usize> {
+        actual_paths
+            .iter()
+            .position(|p| fs::metadata(p).is_ok_and(|m| m.is_file() && m.len() == self.size))
+    }
+}
+
+impl Duplicates {
+    /// Total space used by all but one file of each group, in bytes.
+    pub fn wasted_space(&self) -> u64 {
+        self.groups.iter().map(|g| g.wasted_space()).sum()
+    }
+
+    /// Number of files that are duplicates of another one.
+    pub fn duplicate_count(&self) -> usize {
+        self.groups.iter().map(|g| g.files.len() - 1).sum()
+    }
+
+    /// Determine which files are already hardlinks to the file that would be kept when linking,
+    /// so that they are not counted as wasted space.
+    ///
+    /// `root_paths` contains the root path of each checksum set's files.
+    pub fn find_linked_files(&mut self, root_paths: &[PathBuf]) {
+        for group in self.groups.iter_mut() {
+            let actual_paths: Vec<PathBuf> = group
+                .files
+                .iter()
+                .map(|f| root_paths[f.checksum_set].join(&f.path))
+                .collect();
+
+            let Some(original_index) = group.original_index(&actual_paths) else {
+                continue;
+            };
+
+            let original_path = &actual_paths[original_index];
+
+            for (index, (file, path)) in group.files.iter_mut().zip(actual_paths.iter()).enumerate() {
+                file.linked = index != original_index
+                    && fs::symlink_metadata(path)
+                        .and_then(|metadata| is_same_file(original_path, &metadata))
+                        .unwrap_or(false);
+            }
+        }
+
+        self.sort_groups();
+    }
+
+    /// Sort groups by wasted space, largest first.
+    fn sort_groups(&mut self) {
+        self.groups.sort_by(|a, b| {
+            b.wasted_space()
+                .cmp(&a.wasted_space())
+                .then_with(|| a.hash.cmp(&b.hash))
+        });
+    }
+
+    /// Replace duplicate files with links to the first file of their group.
+    ///
+    /// `root_paths` contains the root path of each checksum set's files. Each file is
+    /// compared byte for byte with the original before being replaced, and is skipped
+    /// if it differs. Replacing is done by creating the link next to the file, and
+    /// renaming it over the file.
+    pub fn link(&self, root_paths: &[PathBuf], method: LinkMethod) -> LinkReport {
+        let mut report = LinkReport::default();
+
+        for group in self.groups.iter() {
+            let actual_paths: Vec<PathBuf> = group
+                .files
+                .iter()
+                .map(|f| root_paths[f.checksum_set].join(&f.path))
+                .collect();
+
+            // The first file still matching the checksum set is kept as the original.
+            let Some(original_index) = group.original_index(&actual_paths) else {
+                for path in actual_paths {
+                    report
+                        .skipped_files
+                        .insert(path, "No file in its group matches the checksum set.".to_owned());
+                }
+
+                continue;
+            };
+
+            let original_path = &actual_paths[original_index];
+
+            for (index, path) in actual_paths.iter().enumerate() {
+                if index == original_index {
+                    continue;
+                }
+
+                match link_duplicate(original_path, path, method) {
+                    Ok(LinkResult::Linked) => {
+                        report.linked_files.push(path.clone());
+                        report.reclaimed_space += group.size;
+                    }
+                    Ok(LinkResult::Skipped(reason)) => {
+                        report.skipped_files.insert(path.clone(), reason.to_owned());
+                    }
+                    Err(err) => {
+                        report.failed_files.insert(path.clone(), err.to_string());
+                    }
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Print the duplicates, using the specified names for each checksum set.
+    ///
+    /// Names are only shown if there is more than one checksum set.
+    pub fn print(&self, names: &[String]) {
+        println!();
+
+        if !self.groups.is_empty() {
+            println!("-- DUPLICATES --");
+
+            for group in self.groups.iter() {
+                println!("{} bytes, {} {}:", group.size, self.hash_type, group.hash);
+
+                for file in group.files.iter() {
+                    let linked = if file.linked { " (already linked)" } else { "" };
+
+                    if names.len() > 1 {
+                        println!("  {}: {}{linked}", names[file.checksum_set], file.path);
+                    } else {
+                        println!("  {}{linked}", file.path);
+                    }
+                }
+            }
+
+            println!();
+        }
+
+        println!("{} groups of duplicate files.", self.groups.len());
+        println!("{} duplicate files.", self.duplicate_count());
+        println!("{} bytes wasted.", self.wasted_space());
+    }
+}
+
+impl LinkReport {
+    pub fn print(&self) {
+        let mut summary: Vec<String> = Vec::new();
+
+        println!();
+
+        if !self.linked_files.is_empty() {
+            println!("-- LINKED FILES --");
+
+            for p in self.linked_files.iter() {
+                println!("{}", p.display());
+            }
+
+            println!();
+
+            summary.push(format!("{} linked files.", self.linked_files.len()));
+            summary.push(format!("{} bytes reclaimed.", self.reclaimed_space));
+        }
+
+        if !self.skipped_files.is_empty() {
+            println!("-- SKIPPED FILES --");
+
+            for (p, reason) in self.skipped_files.iter() {
+                println!("{} == {}", p.display(), reason);
+            }
+
+            println!();
+
+            summary.push(format!("{} skipped files.", self.skipped_files.len()));
+        }
+
+        if !self.failed_files.is_empty() {
+            println!("-- FAILED FILES --");
+
+            for (p, err) in self.failed_files.iter() {
+                println!("{} == {}", p.display(), err);
+            }
+
+            println!();
+
+            summary.push(format!("{} failed files.", self.failed_files.len()));
+        }
+
+        for line in summary.iter() {
+            println!("{}", line);
+        }
+    }
+}
+
+enum LinkResult {
+    Linked,
+    Skipped(&'static str),
+}
+
+/// Replace a file with a link to the original, if their contents are identical.
+fn link_duplicate(original_path: &Path, path: &Path, method: LinkMethod) -> Result<LinkResult, io::Error> {
+    let metadata = fs::symlink_metadata(path)?;
+
+    if !metadata.is_file() {
+        return Ok(LinkResult::Skipped("Not a regular file."));
+    }
+
+    if is_same_file(original_path, &metadata)? {
+        return Ok(LinkResult::Skipped("Already linked."));
+    }
+
+    if !same_contents(original_path, path)? {
+        return Ok(LinkResult::Skipped("Contents differ from the original."));
+    }
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{file_name}.{}.kecs-tmp", std::process::id()));
+
+    // Creating the link fails without creating anything if the temporary path already exists,
+    // in which case the existing file must not be removed.
+    match method {
+        LinkMethod::Hardlink => fs::hard_link(original_path, &temp_path)?,
+        LinkMethod::Reflink => reflink_copy::reflink(original_path, &temp_path)?,
+    }
+
+    let result = match method {
+        LinkMethod::Hardlink => Ok(()),
+        LinkMethod::Reflink => fs::set_permissions(&temp_path, metadata.permissions()),
+    }
+    .and_then(|_| fs::rename(&temp_path, path));
+
+    if let Err(err) = result {
+        let _ = fs::remove_file(&temp_path);
+
+        return Err(err);
+    }
+
+    Ok(LinkResult::Linked)
+}
+
+#[cfg(unix)]
+fn is_same_file(original_path: &Path, metadata: &fs::Metadata) -> Result<bool, io::Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    let original_metadata = fs::metadata(original_path)?;
+
+    Ok(original_metadata.dev() == metadata.dev() && original_metadata.ino() == metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_original_path: &Path, _metadata: &fs::Metadata) -> Result<bool, io::Error> {
+    Ok(false)
+}
+
+/// Compare the contents of two files byte for byte.
+fn same_contents(a: &Path, b: &Path) -> Result<bool, io::Error> {
+    let mut file_a = fs::File::open(a)?;
+    let mut file_b = fs::File::open(b)?;
+
+    if file_a.metadata()?.len() != file_b.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let mut buf_a = vec![0u8; BUFFER_SIZE];
+    let mut buf_b = vec![0u8; BUFFER_SIZE];
+
+    loop {
+        let bytes_a = read_full(&mut file_a, &mut buf_a)?;
+        let bytes_b = read_full(&mut file_b, &mut buf_b)?;
+
+        if buf_a[..bytes_a] != buf_b[..bytes_b] {
+            return Ok(false);
+        }
+
+        if bytes_a == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Read until the buffer is full or the end of the file is reached, returning the number of bytes read.
+fn read_full(file: &mut fs::File, buf: &mut [u8]) -> Result<usize, io::Error> {
+    let mut total = 0;
+
+    while total < buf.len() {
+        match file.read(&mut buf[total..]) {
+            Ok(0) => break,
+            Ok(bytes) => total += bytes,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{checksum_set, TempDir};
+
+    use super::*;
+
+    fn paths(group: &DuplicateGroup) -> Vec<(usize, &str)> {
+        group.files.iter().map(|f| (f.checksum_set, f.path.as_str())).collect()
+    }
+
+    #[test]
+    fn find_duplicates_in_several_checksum_sets() {
+        let a = checksum_set(&[HashType::Md5], &[("a", "1"), ("b", "22"), ("c", "22"), ("d", "3")]);
+        let b = checksum_set(&[HashType::Sha1, HashType::Md5], &[("e", "22"), ("f", "1"), ("g", "4")]);
+
+        let duplicates = ChecksumSet::find_duplicates(&[a, b]).unwrap();
+
+        assert_eq!(duplicates.hash_type, HashType::Md5);
+        assert_eq!(duplicates.groups.len(), 2);
+        assert_eq!(paths(&duplicates.groups[0]), [(0, "b"), (0, "c"), (1, "e")]);
+        assert_eq!(paths(&duplicates.groups[1]), [(0, "a"), (1, "f")]);
+        assert_eq!(duplicates.duplicate_count(), 3);
+        assert_eq!(duplicates.wasted_space(), 5);
+    }
+
+    #[test]
+    fn find_duplicates_without_common_hash_types() {
+        let a = checksum_set(&[HashType::Md5], &[("a", "1")]);
+        let b = checksum_set(&[HashType::Sha1], &[("a", "1")]);
+
+        assert!(matches!(
+            ChecksumSet::find_duplicates(&[a, b]),
+            Err(Error::NoCommonHashTypes)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn linked_files_are_not_wasted_space() {
+        let dir = TempDir::new();
+        let a = dir.write("a", "11");
+        dir.write("c", "11");
+        fs::hard_link(&a, dir.path().join("b")).unwrap();
+
+        let checksums = checksum_set(&[HashType::Md5], &[("a", "11"), ("b", "11"), ("c", "11")]);
+        let mut duplicates = ChecksumSet::find_duplicates(&[checksums]).unwrap();
+
+        assert_eq!(duplicates.wasted_space(), 4);
+
+        duplicates.find_linked_files(&[dir.path().to_path_buf()]);
+
+        let linked: Vec<bool> = duplicates.groups[0].files.iter().map(|f| f.linked).collect();
+
+        assert_eq!(linked, [false, true, false]);
+        assert_eq!(duplicates.wasted_space(), 2);
+    }
+
+    #[test]
+    fn link_duplicates() {
+        let dir = TempDir::new();
+        dir.write("a", "11");
+        dir.write("b", "11");
+        dir.write("c", "12");
+
+        let checksums = checksum_set(&[HashType::Md5], &[("a", "11"), ("b", "11"), ("c", "11")]);
+        let duplicates = ChecksumSet::find_duplicates(&[checksums]).unwrap();
+
+        let report = duplicates.link(&[dir.path().to_path_buf()], LinkMethod::Hardlink);
+
+        assert_eq!(report.linked_files, [dir.path().join("b")]);
+        assert_eq!(report.reclaimed_space, 2);
+        assert_eq!(report.skipped_files.keys().collect::<Vec<_>>(), [&dir.path().join("c")]);
+        assert!(report.failed_files.is_empty());
+        assert_eq!(fs::read_to_string(dir.path().join("c")).unwrap(), "12");
+
+        if cfg!(unix) {
+            let report = duplicates.link(&[dir.path().to_path_buf()], LinkMethod::Hardlink);
+
+            assert!(report.linked_files.is_empty());
+            assert_eq!(report.skipped_files[&dir.path().join("b")], "Already linked.");
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    fs::{self, FileType, Metadata},
+    io,
+    path::Path,
+};
+
+use serde_derive::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use super::{ChecksumSet, ChecksumSetDiff};
+
+/// Entry in a checksum set other than a regular file.
+///
+/// Only the kind of entry, and the target of symlinks, is recorded.
+/// The contents of special files are never read.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Entry {
+    Symlink { target: String },
+    Directory,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+/// Kind of entry found at a path.
+#[derive(Copy, Clone, Debug, Display, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum EntryKind {
+    File,
+    Symlink,
+    Directory,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl Entry {
+    pub fn kind(&self) -> EntryKind {
+        match self {
+            Self::Symlink { .. } => EntryKind::Symlink,
+            Self::Directory => EntryKind::Directory,
+            Self::Fifo => EntryKind::Fifo,
+            Self::Socket => EntryKind::Socket,
+            Self::BlockDevice => EntryKind::BlockDevice,
+            Self::CharDevice => EntryKind::CharDevice,
+        }
+    }
+
+    /// Read the entry at a path, given its file type.
+    ///
+    /// Returns `None` for regular files, and anything else that cannot be represented.
+    pub(super) fn from_file_type(path: &Path, file_type: FileType) -> Option<Self> {
+        Some(match EntryKind::from_file_type(file_type)? {
+            EntryKind::File => return None,
+            EntryKind::Symlink => Self::Symlink {
+                target: read_link_target(path).ok()?,
+            },
+            EntryKind::Directory => Self::Directory,
+            EntryKind::Fifo => Self::Fifo,
+            EntryKind::Socket => Self::Socket,
+            EntryKind::BlockDevice => Self::BlockDevice,
+            EntryKind::CharDevice => Self::CharDevice,
+        })
+    }
+}
+
+impl EntryKind {
+    pub(super) fn from_file_type(file_type: FileType) -> Option<Self> {
+        if file_type.is_symlink() {
+            return Some(Self::Symlink);
+        } else if file_type.is_dir() {
+            return Some(Self::Directory);
+        } else if file_type.is_file() {
+            return Some(Self::File);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+
+            if file_type.is_fifo() {
+                return Some(Self::Fifo);
+            } else if file_type.is_socket() {
+                return Some(Self::Socket);
+            } else if file_type.is_block_device() {
+                return Some(Self::BlockDevice);
+            } else if file_type.is_char_device() {
+                return Some(Self::CharDevice);
+            }
+        }
+
+        None
+    }
+}
+
+fn read_link_target(path: &Path) -> Result<String, io::Error> {
+    Ok(fs::read_link(path)?.to_string_lossy().into_owned())
+}
+
+impl ChecksumSet {
+    /// Read the metadata of a file or other entry on disk, following
+    /// symlinks only if the checksum set does not record them.
+    ///
+    /// Checksum sets without any recorded entries predate symlinks being
+    /// recorded. They were generated without following symlinks, which were
+    /// skipped, so they only contain regular files. As there is nothing to compare
+    /// entries with, symlinks are followed for them.
+    pub(super) fn entry_metadata(&self, path: &Path) -> Result<Metadata, io::Error> {
+        if self.follow_symlinks || self.entries.is_none() {
+            fs::metadata(path)
+        } else {
+            fs::symlink_metadata(path)
+        }
+    }
+
+    /// Compare a recorded entry with the actual entry on disk, recording any differences in `diff`.
+    ///
+    /// Entries that are missing are not recorded, and `false` is returned.
+    pub(super) fn verify_entry(
+        &self,
+        path: &str,
+        expected: &Entry,
+        actual_path: &Path,
+        diff: &mut ChecksumSetDiff,
+    ) -> bool {
+        let Ok(metadata) = self.entry_metadata(actual_path) else {
+            return false;
+        };
+
+        let actual_kind = EntryKind::from_file_type(metadata.file_type());
+
+        match (expected, actual_kind) {
+            (Entry::Symlink { target }, Some(EntryKind::Symlink)) => {
+                let actual_target = read_link_target(actual_path).unwrap_or_default();
+
+                if *target != actual_target {
+                    diff.differing_link_targets
+                        .insert(path.to_owned(), (target.clone(), actual_target));
+                }
+            }
+            (expected, Some(actual_kind)) if expected.kind() != actual_kind => {
+                diff.differing_kinds
+                    .insert(path.to_owned(), (expected.kind(), actual_kind));
+            }
+            _ => {}
+        }
+
+        true
+    }
+}
+
+/// Compare the entries of two checksum sets, recording any differences in `diff`.
+///
+/// Entries are only compared if both checksum sets record them. Paths that are a regular
+/// file on one side and another kind of entry on the other are reported as differing kinds.
+pub(super) fn compare_entries(a: &ChecksumSet, b: &ChecksumSet, diff: &mut ChecksumSetDiff) {
+    let (Some(a_entries), Some(b_entries)) = (&a.entries, &b.entries) else {
+        return;
+    };
+
+    for (path, a_entry) in a_entries.iter() {
+        match b_entries.get(path) {
+            Some(b_entry) => match (a_entry, b_entry) {
+                (Entry::Symlink { target: a_target }, Entry::Symlink { target: b_target }) => {
+                    if a_target != b_target {
+                        diff.differing_link_targets
+                            .insert(path.clone(), (a_target.clone(), b_target.clone()));
+                    }
+                }
+                _ => {
+                    if a_entry.kind() != b_entry.kind() {
+                        diff.differing_kinds
+                            .insert(path.clone(), (a_entry.kind(), b_entry.kind()));
+                    }
+                }
+            },
+            None if b.files.contains_key(path) => {
+                diff.differing_kinds
+                    .insert(path.clone(), (a_entry.kind(), EntryKind::File));
+            }
+            None if !b.unreadable_files.contains(path) => {
+                diff.additional_entries.insert(path.clone(), a_entry.kind());
+            }
+            None => {}
+        }
+    }
+
+    for (path, b_entry) in b_entries.iter() {
+        if a_entries.contains_key(path) {
+            continue;
+        }
+
+        if a.files.contains_key(path) {
+            diff.differing_kinds
+                .insert(path.clone(), (EntryKind::File, b_entry.kind()));
+        } else if !a.unreadable_files.contains(path) {
+            diff.missing_entries.insert(path.clone(), b_entry.kind());
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde_derive::{Deserialize, Serialize};
+
+use tracing::warn;
+
+use crate::{util, Error};
+
+/// Name of ignore files that are honored anywhere in a scanned directory tree.
+pub const IGNORE_FILENAME: &str = ".kecsignore";
+
+/// Filter determining which files are included in a checksum set.
+///
+/// Include and exclude patterns are globs. Patterns containing a `/` are
+/// matched against the path relative to the root, while patterns without one
+/// are matched against the file or directory name only.
+/// Ignore patterns use `.gitignore` syntax.
+///
+/// In addition to this, any `.kecsignore` files found while scanning
+/// are honored, in the same way as `.gitignore` files. Their patterns are not
+/// stored in the filter, but read again whenever the directory tree is scanned.
+/// `.gitignore` files are not honored.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct FileFilter {
+    /// If not empty, only files matching one of these patterns are included.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Files and directories matching any of these patterns are excluded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Ignore patterns, in `.gitignore` syntax.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+}
+
+/// Compiled form of a `FileFilter`.
+#[derive(Debug)]
+pub(super) struct FilterMatcher {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    ignore: Gitignore,
+}
+
+impl FileFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.ignore.is_empty()
+    }
+
+    /// Add the patterns from a `.gitignore`-style file.
+    ///
+    /// As in a `.gitignore` file, the patterns are relative to the directory containing the file.
+    /// If it is inside `root_path`, they are rewritten to be relative to the root, so that they only
+    /// match below that directory. Otherwise, they are added as they are, and matched relative to the root.
+    pub fn add_ignore_file(&mut self, path: &Path, root_path: &Path) -> Result<(), Error> {
+        let content = std::fs::read_to_string(path).map_err(|source| Error::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let patterns = content
+            .lines()
+            .map(|line| line.trim_end())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        let path = util::normalize_path(path);
+        let root_path = util::normalize_path(root_path);
+
+        let dir = path
+            .parent()
+            .and_then(|dir| dir.strip_prefix(&root_path).ok())
+            .map(util::unixify_path);
+
+        match dir {
+            Some(dir) if !dir.is_empty() => {
+                self.ignore
+                    .extend(patterns.map(|pattern| prefix_ignore_pattern(pattern, &dir)));
+            }
+            Some(_) => self.ignore.extend(patterns.map(String::from)),
+            None => {
+                warn!(
+                    "Ignore file is outside of the root path, so its patterns are matched relative to the root: {}",
+                    path.display()
+                );
+
+                self.ignore.extend(patterns.map(String::from));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn compile(&self) -> Result<FilterMatcher, Error> {
+        let include = if self.include.is_empty() {
+            None
+        } else {
+            Some(build_glob_set(&self.include)?)
+        };
+
+        let exclude = build_glob_set(&self.exclude)?;
+
+        let mut ignore = GitignoreBuilder::new("");
+
+        for pattern in self.ignore.iter() {
+            ignore
+                .add_line(None, pattern)
+                .map_err(|err| invalid_pattern(pattern, err))?;
+        }
+
+        let ignore = ignore
+            .build()
+            .map_err(|err| invalid_pattern(&self.ignore.join(", "), err))?;
+
+        Ok(FilterMatcher {
+            include,
+            exclude,
+            ignore,
+        })
+    }
+}
+
+impl Default for FilterMatcher {
+    fn default() -> Self {
+        Self {
+            include: None,
+            exclude: GlobSet::empty(),
+            ignore: Gitignore::empty(),
+        }
+    }
+}
+
+impl FilterMatcher {
+    /// Determine whether a file or directory should be skipped.
+    ///
+    /// The path must be relative to the root.
+    pub fn is_excluded(&self, rel_path: &Path, is_dir: bool) -> bool {
+        self.exclude.is_match(util::unixify_path(rel_path)) || self.ignore.matched(rel_path, is_dir).is_ignore()
+    }
+
+    /// Determine whether a file that is not excluded should be included.
+    ///
+    /// The path must be relative to the root.
+    pub fn is_included(&self, rel_path: &Path) -> bool {
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.is_match(util::unixify_path(rel_path)))
+    }
+}
+
+/// Rewrite a `.gitignore` pattern from an ignore file in the specified directory,
+/// relative to the root, so that it only matches below that directory.
+///
+/// Patterns containing a `/` other than at the end are anchored to the directory,
+/// while other patterns match at any depth below it.
+fn prefix_ignore_pattern(pattern: &str, dir: &str) -> String {
+    let (negation, pattern) = match pattern.strip_prefix('!') {
+        Some(pattern) => ("!", pattern),
+        None => ("", pattern),
+    };
+
+    let is_anchored = pattern.trim_end_matches('/').contains('/');
+
+    // Escape any characters in the directory name that have a special meaning in patterns.
+    let dir: String = dir
+        .chars()
+        .flat_map(|c| match c {
+            '*' | '?' | '[' | '\\' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect();
+
+    if is_anchored {
+        format!("{negation}/{dir}/{}", pattern.trim_start_matches('/'))
+    } else {
+        format!("{negation}/{dir}/**/{pattern}")
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet, Error> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        let pattern = if pattern.contains('/') {
+            pattern.trim_start_matches('/').to_owned()
+        } else {
+            format!("**/{pattern}")
+        };
+
+        builder.add(Glob::new(&pattern).map_err(|err| invalid_pattern(&pattern, err))?);
+    }
+
+    builder
+        .build()
+        .map_err(|err| invalid_pattern(&patterns.join(", "), err))
+}
+
+fn invalid_pattern<E: std::error::Error>(pattern: &str, err: E) -> Error {
+    Error::InvalidPattern {
+        pattern: pattern.to_owned(),
+        message: err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TempDir;
+
+    use super::*;
+
+    fn ignore_filter(patterns: &[&str]) -> FilterMatcher {
+        FileFilter {
+            ignore: patterns.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        }
+        .compile()
+        .unwrap()
+    }
+
+    #[test]
+    fn prefix_ignore_patterns() {
+        for (pattern, prefixed) in [
+            ("*.tmp", "/sub/dir/**/*.tmp"),
+            ("build/", "/sub/dir/**/build/"),
+            ("/build", "/sub/dir/build"),
+            ("a/b", "/sub/dir/a/b"),
+            ("a/b/", "/sub/dir/a/b/"),
+            ("!keep.tmp", "!/sub/dir/**/keep.tmp"),
+            ("!/a/b", "!/sub/dir/a/b"),
+        ] {
+            assert_eq!(prefix_ignore_pattern(pattern, "sub/dir"), prefixed, "{pattern}");
+        }
+
+        assert_eq!(prefix_ignore_pattern("*.tmp", "a[1]*"), "/a\\[1]\\*/**/*.tmp");
+    }
+
+    #[test]
+    fn prefixed_patterns_only_match_below_directory() {
+        let matcher = ignore_filter(&[
+            &prefix_ignore_pattern("*.tmp", "sub"),
+            &prefix_ignore_pattern("/build", "sub"),
+            &prefix_ignore_pattern("!keep.tmp", "sub"),
+        ]);
+
+        for (path, is_dir, excluded) in [
+            ("sub/a.tmp", false, true),
+            ("sub/x/a.tmp", false, true),
+            ("a.tmp", false, false),
+            ("other/a.tmp", false, false),
+            ("sub/x/keep.tmp", false, false),
+            ("sub/build", true, true),
+            ("sub/x/build", true, false),
+            ("build", true, false),
+        ] {
+            assert_eq!(matcher.is_excluded(Path::new(path), is_dir), excluded, "{path}");
+        }
+    }
+
+    #[test]
+    fn add_ignore_files() {
+        let dir = TempDir::new();
+        let root_file = dir.write("root/.ignore", "# Comment\n\n*.tmp\n");
+        let sub_file = dir.write("root/sub/.ignore", "/build\n");
+        let outside_file = dir.write("outside.ignore", "/cache\n");
+
+        let mut filter = FileFilter::default();
+        let root_path = dir.path().join("root");
+
+        filter.add_ignore_file(&root_file, &root_path).unwrap();
+        filter.add_ignore_file(&sub_file, &root_path).unwrap();
+        filter.add_ignore_file(&outside_file, &root_path).unwrap();
+
+        assert_eq!(filter.ignore, ["*.tmp", "/sub/build", "/cache"]);
+
+        assert!(matches!(
+            filter.add_ignore_file(&dir.path().join("missing"), &root_path),
+            Err(Error::Read { .. })
+        ));
+    }
+
+    #[test]
+    fn include_and_exclude_patterns() {
+        let matcher = FileFilter {
+            include: vec!["docs/**".to_owned(), "*.md".to_owned()],
+            exclude: vec!["Thumbs.db".to_owned(), "/docs/old".to_owned()],
+            ignore: Vec::new(),
+        }
+        .compile()
+        .unwrap();
+
+        assert!(matcher.is_included(Path::new("docs/a.txt")));
+        assert!(matcher.is_included(Path::new("x/README.md")));
+        assert!(!matcher.is_included(Path::new("x/a.txt")));
+
+        assert!(matcher.is_excluded(Path::new("docs/Thumbs.db"), false));
+        assert!(matcher.is_excluded(Path::new("docs/old"), true));
+        assert!(!matcher.is_excluded(Path::new("x/docs/old"), true));
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use crate::checksum_set::{ChecksumSet, FileInfo, HashType};
+
+/// Parse a checksum file in GNU coreutils format, BSD-style tagged format, or a mix of both.
+///
+/// Tagged lines specify their own hash type. For untagged lines, `hash_type` is used if
+/// specified, otherwise the hash type is guessed from the length of the digest.
+/// Digests whose length does not match their hash type are rejected.
+pub(crate) fn parse_sum_file(content: &str, hash_type: Option<HashType>) -> Result<ChecksumSet, String> {
+    let mut hash_types: Vec<HashType> = Vec::new();
+    let mut files: BTreeMap<String, FileInfo> = BTreeMap::new();
+
+    let mut in_pgp_header = false;
+    let mut in_pgp_signature = false;
+
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+
+        // Skip PGP armor, in case the checksum file is clearsigned.
+        if line == "-----BEGIN PGP SIGNED MESSAGE-----" {
+            in_pgp_header = true;
+            continue;
+        } else if in_pgp_header {
+            in_pgp_header = !line.is_empty();
+            continue;
+        } else if line == "-----BEGIN PGP SIGNATURE-----" {
+            in_pgp_signature = true;
+            continue;
+        } else if in_pgp_signature {
+            in_pgp_signature = line != "-----END PGP SIGNATURE-----";
+            continue;
+        }
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (line_hash_type, hash, path) = if let Some((tag, hash, path)) = parse_bsd_line(line) {
+            let line_hash_type = HashType::from_bsd_tag(&tag)
+                .ok_or_else(|| format!("Unsupported hash type '{tag}' on line {}.", line_number + 1))?;
+
+            (line_hash_type, hash, path)
+        } else if let Some((hash, path)) = parse_gnu_line(line) {
+            let line_hash_type = hash_type
+                .or_else(|| HashType::from_digest_length(hash.len()))
+                .ok_or_else(|| format!("Could not determine hash type on line {}.", line_number + 1))?;
+
+            (line_hash_type, hash, path)
+        } else {
+            return Err(format!("Improperly formatted checksum line {}.", line_number + 1));
+        };
+
+        if hash.len() != line_hash_type.digest_length() {
+            return Err(format!(
+                "Invalid {line_hash_type} checksum length on line {}: {} characters, expected {}.",
+                line_number + 1,
+                hash.len(),
+                line_hash_type.digest_length()
+            ));
+        }
+
+        if !hash_types.contains(&line_hash_type) {
+            hash_types.push(line_hash_type);
+        }
+
+        let path = path.strip_prefix("./").map(String::from).unwrap_or(path);
+
+        files
+            .entry(path)
+            .or_insert_with(|| FileInfo {
+                size: None,
+                hashes: BTreeMap::new(),
+                ..Default::default()
+            })
+            .hashes
+            .insert(line_hash_type, hash);
+    }
+
+    if files.is_empty() {
+        return Err("No properly formatted checksum lines found.".to_owned());
+    }
+
+    for hash_type in hash_types.iter() {
+        if let Some(path) = files
+            .iter()
+            .find(|(_, fi)| !fi.hashes.contains_key(hash_type))
+            .map(|(p, _)| p)
+        {
+            return Err(format!("No {hash_type} checksum found for '{path}'."));
+        }
+    }
+
+    Ok(ChecksumSet {
+        hash_types,
+        files,
+        unreadable_files: Default::default(),
+        scan_paths: Default::default(),
+        filter: Default::default(),
+        metadata: Default::default(),
+        entries: None,
+        follow_symlinks: false,
+    })
+}
+
+/// Write a checksum set in GNU coreutils format, or BSD-style tagged format if `bsd` is true.
+pub(crate) fn write_sum_file<W: Write>(
+    checksums: &ChecksumSet,
+    hash_type: HashType,
+    bsd: bool,
+    mut writer: W,
+) -> Result<(), io::Error> {
+    for (path, fi) in checksums.files.iter() {
+        let Some(hash) = fi.hash(hash_type) else {
+            continue;
+        };
+
+        writeln!(writer, "{}", format_sum_line(path, hash, hash_type, bsd))?;
+    }
+
+    writer.flush()
+}
+
+/// Format a line of a checksum file in GNU coreutils format, or BSD-style tagged format if `bsd` is true,
+/// escaping the path if necessary.
+pub fn format_sum_line(path: &str, hash: &str, hash_type: HashType, bsd: bool) -> String {
+    let (prefix, path) = escape_path(path);
+
+    if bsd {
+        format!("{prefix}{} ({path}) = {hash}", hash_type.bsd_tag())
+    } else {
+        format!("{prefix}{hash}  {path}")
+    }
+}
+
+/// Parse a line in BSD-style tagged format, such as `SHA256 (filename) = hash`.
+///
+/// Returns the tag, hash and path.
+pub(crate) fn parse_bsd_line(line: &str) -> Option<(String, String, String)> {
+    let (escaped, line) = strip_escape_prefix(line);
+
+    let (tag, rest) = line.split_once(" (")?;
+    let (path, hash) = rest.rsplit_once(") = ")?;
+
+    if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') || !is_hex(hash) {
+        return None;
+    }
+
+    Some((tag.to_owned(), hash.to_ascii_lowercase(), unescape_path(path, escaped)?))
+}
+
+/// Parse a line in GNU coreutils format, such as `hash  filename` or `hash *filename`.
+///
+/// Returns the hash and path.
+pub(crate) fn parse_gnu_line(line: &str) -> Option<(String, String)> {
+    let (escaped, line) = strip_escape_prefix(line);
+
+    let (hash, rest) = line.split_once(' ')?;
+
+    // The character following the space indicates text (' ') or binary ('*') mode,
+    // which makes no difference to the hash.
+    let path = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*'))?;
+
+    if path.is_empty() || !is_hex(hash) {
+        return None;
+    }
+
+    Some((hash.to_ascii_lowercase(), unescape_path(path, escaped)?))
+}
+
+fn strip_escape_prefix(line: &str) -> (bool, &str) {
+    match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    }
+}
+
+fn unescape_path(path: &str, escaped: bool) -> Option<String> {
+    if !escaped {
+        return Some(path.to_owned());
+    }
+
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                '\\' => unescaped.push('\\'),
+                'n' => unescaped.push('\n'),
+                'r' => unescaped.push('\r'),
+                _ => return None,
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+
+    Some(unescaped)
+}
+
+/// Escape a path the same way GNU coreutils does.
+///
+/// Returns the prefix to put at the start of the line, which is a backslash
+/// if the path needed escaping, and the escaped path.
+fn escape_path(path: &str) -> (&'static str, String) {
+    if !path.contains(['\\', '\n', '\r']) {
+        return ("", path.to_owned());
+    }
+
+    let escaped = path.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r");
+
+    ("\\", escaped)
+}
+
+fn is_hex(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str, hash_type: Option<HashType>) -> Result<ChecksumSet, String> {
+        parse_sum_file(content, hash_type)
+    }
+
+    #[test]
+    fn parse_untagged_lines() {
+        let md5 = HashType::Md5.hash_bytes(b"a");
+        let sha256 = HashType::Sha256.hash_bytes(b"b");
+
+        let checksums = parse(&format!("{md5}  ./a\n{} *b\n", md5.to_uppercase()), None).unwrap();
+
+        assert_eq!(checksums.hash_types, vec![HashType::Md5]);
+        assert_eq!(checksums.files["a"].hash(HashType::Md5), Some(md5.as_str()));
+        assert_eq!(checksums.files["b"].hash(HashType::Md5), Some(md5.as_str()));
+
+        let checksums = parse(&format!("{sha256}  a\n"), None).unwrap();
+
+        assert_eq!(checksums.hash_types, vec![HashType::Sha256]);
+    }
+
+    #[test]
+    fn parse_bsd_tags() {
+        for hash_type in [
+            HashType::Blake2b256,
+            HashType::Blake2b512,
+            HashType::Md5,
+            HashType::Sha1,
+            HashType::Sha224,
+            HashType::Sha384,
+            HashType::Sha512,
+            HashType::Sha3_512,
+        ] {
+            let hash = hash_type.hash_bytes(b"a");
+            let checksums = parse(&format!("{} (dir/a b) = {hash}\n", hash_type.bsd_tag()), None).unwrap();
+
+            assert_eq!(checksums.hash_types, vec![hash_type]);
+            assert_eq!(checksums.files["dir/a b"].hash(hash_type), Some(hash.as_str()));
+        }
+
+        let sha256 = HashType::Sha256.hash_bytes(b"a");
+
+        assert!(parse(&format!("SHA2-256 (a) = {sha256}\n"), None).is_ok());
+        assert_eq!(
+            parse(&format!("WHIRLPOOL (a) = {sha256}\n"), None).unwrap_err(),
+            "Unsupported hash type 'WHIRLPOOL' on line 1."
+        );
+    }
+
+    #[test]
+    fn parse_mixed_hash_types() {
+        let md5_a = HashType::Md5.hash_bytes(b"a");
+        let md5_b = HashType::Md5.hash_bytes(b"b");
+        let sha1_a = HashType::Sha1.hash_bytes(b"a");
+        let sha1_b = HashType::Sha1.hash_bytes(b"b");
+
+        let content = format!("{md5_a}  a\nSHA1 (a) = {sha1_a}\n{md5_b}  b\nSHA1 (b) = {sha1_b}\n");
+        let checksums = parse(&content, None).unwrap();
+
+        assert_eq!(checksums.hash_types, vec![HashType::Md5, HashType::Sha1]);
+        assert_eq!(checksums.files["b"].hash(HashType::Sha1), Some(sha1_b.as_str()));
+
+        // Every file must have every hash type.
+        let content = format!("{md5_a}  a\nSHA1 (a) = {sha1_a}\n{md5_b}  b\n");
+
+        assert_eq!(parse(&content, None).unwrap_err(), "No sha1 checksum found for 'b'.");
+    }
+
+    #[test]
+    fn parse_rejects_length_mismatches() {
+        let sha256 = HashType::Sha256.hash_bytes(b"a");
+        let sha512 = HashType::Sha512.hash_bytes(b"a");
+
+        // A truncated digest with a tag.
+        assert_eq!(
+            parse(&format!("{sha256}  a\nSHA256 (b) = {}\n", &sha256[1..]), None).unwrap_err(),
+            "Invalid sha256 checksum length on line 2: 63 characters, expected 64."
+        );
+
+        // A digest not matching the hash type guessed from the file name.
+        assert_eq!(
+            parse(&format!("{sha512}  a\n"), Some(HashType::Sha256)).unwrap_err(),
+            "Invalid sha256 checksum length on line 1: 128 characters, expected 64."
+        );
+
+        // A digest length not matching any hash type.
+        assert_eq!(
+            parse("0123456789  a\n", None).unwrap_err(),
+            "Could not determine hash type on line 1."
+        );
+    }
+
+    #[test]
+    fn escaped_paths_round_trip() {
+        let sha256 = HashType::Sha256.hash_bytes(b"a");
+
+        for bsd in [false, true] {
+            let line = format_sum_line("dir/new\nline\\back\rslash", &sha256, HashType::Sha256, bsd);
+
+            assert!(line.starts_with('\\'));
+            assert!(!line.contains('\n'));
+
+            let checksums = parse(&format!("{line}\n"), None).unwrap();
+
+            assert!(checksums.files.contains_key("dir/new\nline\\back\rslash"));
+        }
+
+        // Invalid escape sequences make the line improperly formatted.
+        assert_eq!(
+            parse(&format!("\\{sha256}  a\\tb\n"), None).unwrap_err(),
+            "Improperly formatted checksum line 1."
+        );
+    }
+
+    #[test]
+    fn parse_skips_pgp_armor_and_comments() {
+        let sha256 = HashType::Sha256.hash_bytes(b"a");
+        let content = format!(
+            "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\n# comment\n{sha256}  a\n\
+             -----BEGIN PGP SIGNATURE-----\n\nabc\n-----END PGP SIGNATURE-----\n"
+        );
+
+        let checksums = parse(&content, None).unwrap();
+
+        assert_eq!(checksums.files.keys().collect::<Vec<_>>(), vec!["a"]);
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use tracing::warn;
+
+use crate::checksum_set::{ChecksumSet, FileInfo, HashType};
+
+pub(crate) const HASHDEEP_HEADER: &str = "%%%% HASHDEEP-1.0";
+
+/// Hash types supported by hashdeep, in the order hashdeep writes them.
+pub(crate) const HASHDEEP_HASH_TYPES: &[HashType] = &[HashType::Md5, HashType::Sha1, HashType::Sha256];
+
+/// Parse a file in hashdeep format.
+///
+/// Hash types that are not supported by KeCS, such as tiger and whirlpool, are ignored.
+pub(crate) fn parse_hashdeep_file(content: &str) -> Result<ChecksumSet, String> {
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .map(|(n, l)| (n + 1, l.trim_end_matches('\r')));
+
+    match lines.next() {
+        Some((_, line)) if line == HASHDEEP_HEADER => {}
+        _ => return Err("Missing hashdeep header.".to_owned()),
+    }
+
+    let columns: Vec<&str> = match lines.next() {
+        Some((_, line)) if line.starts_with("%%%% ") => line[5..].split(',').collect(),
+        _ => return Err("Missing hashdeep column header.".to_owned()),
+    };
+
+    if columns.first() != Some(&"size") || columns.last() != Some(&"filename") {
+        return Err(format!("Unsupported hashdeep columns: {}", columns.join(",")));
+    }
+
+    // Map each hash column to its hash type, if it is supported.
+    let column_hash_types: Vec<Option<HashType>> = columns[1..columns.len() - 1]
+        .iter()
+        .map(|c| c.parse::<HashType>().ok())
+        .collect();
+
+    let hash_types: Vec<HashType> = column_hash_types.iter().flatten().copied().collect();
+
+    if hash_types.is_empty() {
+        return Err("No supported hash types in hashdeep file.".to_owned());
+    }
+
+    let mut files: BTreeMap<String, FileInfo> = BTreeMap::new();
+
+    for (line_number, line) in lines {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // The file name is the last column, and may itself contain commas.
+        let fields: Vec<&str> = line.splitn(columns.len(), ',').collect();
+
+        if fields.len() != columns.len() {
+            return Err(format!("Improperly formatted hashdeep line {line_number}."));
+        }
+
+        let size: u64 = fields[0]
+            .parse()
+            .map_err(|_| format!("Invalid size on hashdeep line {line_number}."))?;
+
+        let hashes: BTreeMap<HashType, String> = column_hash_types
+            .iter()
+            .zip(fields[1..].iter())
+            .filter_map(|(ht, hash)| ht.map(|ht| (ht, hash.to_ascii_lowercase())))
+            .collect();
+
+        let path = fields[columns.len() - 1];
+        let path = path.strip_prefix("./").unwrap_or(path);
+
+        files.insert(
+            path.to_owned(),
+            FileInfo {
+                size: Some(size),
+                hashes,
+                ..Default::default()
+            },
+        );
+    }
+
+    Ok(ChecksumSet {
+        hash_types,
+        files,
+        unreadable_files: Default::default(),
+        scan_paths: Default::default(),
+        filter: Default::default(),
+        metadata: Default::default(),
+        entries: None,
+        follow_symlinks: false,
+    })
+}
+
+/// Write a checksum set in hashdeep format, using the specified hash types.
+pub(crate) fn write_hashdeep_file<W: Write>(
+    checksums: &ChecksumSet,
+    hash_types: &[HashType],
+    mut writer: W,
+) -> Result<(), io::Error> {
+    let columns: Vec<String> = hash_types.iter().map(|ht| ht.to_string()).collect();
+
+    writeln!(writer, "{HASHDEEP_HEADER}")?;
+    writeln!(writer, "%%%% size,{},filename", columns.join(","))?;
+    writeln!(writer, "## Generated by KeCS {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(writer, "##")?;
+
+    for (path, fi) in checksums.files.iter() {
+        let hashes: Option<Vec<&str>> = hash_types.iter().map(|ht| fi.hash(*ht)).collect();
+
+        let (Some(size), Some(hashes)) = (fi.size, hashes) else {
+            continue;
+        };
+
+        if path.contains(['\n', '\r']) {
+            warn!("'{path}' cannot be represented in a hashdeep file. Skipping.");
+            continue;
+        }
+
+        writeln!(writer, "{size},{},{path}", hashes.join(","))?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::checksum_set;
+
+    use super::*;
+
+    fn header(columns: &str) -> String {
+        format!("{HASHDEEP_HEADER}\n%%%% {columns}\n## Invoked from: /\n##\n")
+    }
+
+    #[test]
+    fn parse_lines() {
+        let md5 = HashType::Md5.hash_bytes(b"a");
+        let sha256 = HashType::Sha256.hash_bytes(b"a");
+
+        let content = format!(
+            "{}1,{},{},ffff,./dir/a,b\r\n1,{md5},{sha256},ffff,c\n",
+            header("size,md5,sha256,tiger,filename"),
+            md5.to_uppercase(),
+            sha256
+        );
+
+        let checksums = parse_hashdeep_file(&content).unwrap();
+
+        assert_eq!(checksums.hash_types, [HashType::Md5, HashType::Sha256]);
+        assert_eq!(checksums.files.keys().collect::<Vec<_>>(), ["c", "dir/a,b"]);
+        assert_eq!(checksums.files["dir/a,b"].size, Some(1));
+        assert_eq!(checksums.files["dir/a,b"].hash(HashType::Md5), Some(md5.as_str()));
+        assert_eq!(checksums.files["dir/a,b"].hash(HashType::Sha256), Some(sha256.as_str()));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_files() {
+        for (content, error) in [
+            ("size,md5,filename\n".to_owned(), "Missing hashdeep header."),
+            (format!("{HASHDEEP_HEADER}\n"), "Missing hashdeep column header."),
+            (
+                header("md5,size,filename"),
+                "Unsupported hashdeep columns: md5,size,filename",
+            ),
+            (
+                header("size,tiger,filename"),
+                "No supported hash types in hashdeep file.",
+            ),
+            (
+                format!("{}1,a\n", header("size,md5,filename")),
+                "Improperly formatted hashdeep line 5.",
+            ),
+            (
+                format!("{}x,a,b\n", header("size,md5,filename")),
+                "Invalid size on hashdeep line 5.",
+            ),
+        ] {
+            assert_eq!(parse_hashdeep_file(&content).unwrap_err(), error);
+        }
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let checksums = checksum_set(
+            HASHDEEP_HASH_TYPES,
+            &[("a,b", "1"), ("dir/c d", "22"), ("new\nline", "3")],
+        );
+
+        let mut output = Vec::new();
+        write_hashdeep_file(&checksums, HASHDEEP_HASH_TYPES, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with(&format!("{HASHDEEP_HEADER}\n%%%% size,md5,sha1,sha256,filename\n")));
+        assert!(!output.contains("new"));
+
+        let parsed = parse_hashdeep_file(&output).unwrap();
+
+        assert_eq!(parsed.hash_types, HASHDEEP_HASH_TYPES);
+        assert_eq!(parsed.files.keys().collect::<Vec<_>>(), ["a,b", "dir/c d"]);
+        assert_eq!(parsed.files["dir/c d"].size, Some(2));
+        assert_eq!(parsed.files["dir/c d"].hashes, checksums.files["dir/c d"].hashes);
+    }
+}
@@ -0,0 +1,260 @@
+mod gnu;
+mod hashdeep;
+mod sfv;
+
+use std::path::Path;
+
+use strum_macros::{Display, EnumString};
+
+use super::HashType;
+
+pub use self::gnu::format_sum_line;
+pub(crate) use self::gnu::*;
+pub(crate) use self::hashdeep::*;
+pub(crate) use self::sfv::*;
+
+/// File format of a checksum set.
+#[derive(Copy, Clone, Debug, Display, EnumString, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum ChecksumSetFormat {
+    /// KeCS JSON format.
+    Kecs,
+    /// GNU coreutils format, as produced by `sha256sum` and friends.
+    Gnu,
+    /// BSD-style tagged format, as produced by `sha256sum --tag`.
+    Bsd,
+    /// Simple File Verification format, containing CRC32 checksums.
+    Sfv,
+    /// Format used by hashdeep, containing file sizes and MD5, SHA-1 and SHA-256 hashes.
+    Hashdeep,
+}
+
+impl ChecksumSetFormat {
+    /// Guess the format of a checksum set file from its name.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let filename = path.file_name()?.to_string_lossy().to_lowercase();
+
+        if filename.ends_with(".json") {
+            Some(Self::Kecs)
+        } else if filename.ends_with(".sfv") {
+            Some(Self::Sfv)
+        } else if filename.ends_with(".hashdeep") {
+            Some(Self::Hashdeep)
+        } else if HashType::from_filename(&filename).is_some() {
+            Some(Self::Gnu)
+        } else {
+            None
+        }
+    }
+
+    /// Detect the format of a checksum set from the start of its content.
+    pub(super) fn detect(content: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(content);
+
+        if text.starts_with(HASHDEEP_HEADER) {
+            return Self::Hashdeep;
+        }
+
+        let first_line = text
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'));
+
+        match first_line {
+            Some(line) if line.starts_with('{') => Self::Kecs,
+            Some(line) if line.starts_with(';') => Self::Sfv,
+            Some(line) if parse_bsd_line(line).is_some() => Self::Bsd,
+            Some(line) if parse_gnu_line(line).is_none() && parse_sfv_line(line).is_some() => Self::Sfv,
+            _ => Self::Gnu,
+        }
+    }
+
+    /// Hash type implied by the format, for formats that only support a single one.
+    pub fn implied_hash_type(&self) -> Option<HashType> {
+        match self {
+            Self::Sfv => Some(HashType::Crc32),
+            _ => None,
+        }
+    }
+}
+
+impl HashType {
+    /// Guess the hash type of a checksum file from its name,
+    /// for example `SHA256SUMS`, `release-b2sums.txt` or `file.md5`.
+    ///
+    /// Only names ending with the name of a hash type followed by `SUMS` or `SUM`, or with
+    /// the name of a hash type as the extension, are recognized, so that names merely
+    /// containing one (such as `md5-to-sha256.json`) are not mistaken for checksum files.
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        let filename = filename.to_lowercase();
+        let name = filename.strip_suffix(".txt").unwrap_or(&filename);
+
+        // Names like `SHA256SUMS` or `release-sha256sums`, starting at a word boundary.
+        let word_starts = std::iter::once(0).chain(name.match_indices(['.', '-', '_', ' ']).map(|(i, _)| i + 1));
+
+        for start in word_starts {
+            let word = &name[start..];
+
+            if let Some(hash_type) = word
+                .strip_suffix("sums")
+                .or_else(|| word.strip_suffix("sum"))
+                .and_then(Self::from_name_hint)
+            {
+                return Some(hash_type);
+            }
+        }
+
+        // Names like `file.sha256`.
+        let (_, extension) = name.rsplit_once('.')?;
+
+        Self::from_name_hint(extension)
+    }
+
+    /// Hash type referred to by a name used in checksum file names, such as `sha256` or `b2`.
+    fn from_name_hint(hint: &str) -> Option<Self> {
+        match hint {
+            "sha3-256" | "sha3_256" => Some(HashType::Sha3_256),
+            "sha3-512" | "sha3_512" => Some(HashType::Sha3_512),
+            "sha224" => Some(HashType::Sha224),
+            "sha256" => Some(HashType::Sha256),
+            "sha384" => Some(HashType::Sha384),
+            "sha512" => Some(HashType::Sha512),
+            "sha1" => Some(HashType::Sha1),
+            "md5" => Some(HashType::Md5),
+            "blake2b-256" | "blake2b256" => Some(HashType::Blake2b256),
+            "blake2b" | "blake2b-512" | "blake2b512" | "b2" => Some(HashType::Blake2b512),
+            "blake2s" | "blake2s-256" | "blake2s256" => Some(HashType::Blake2s256),
+            "blake3" | "b3" => Some(HashType::Blake3),
+            "crc32" => Some(HashType::Crc32),
+            _ => None,
+        }
+    }
+
+    /// Guess the hash type of a hex digest from its length,
+    /// picking the most commonly used hash type for each length.
+    pub(super) fn from_digest_length(length: usize) -> Option<Self> {
+        match length {
+            8 => Some(HashType::Crc32),
+            32 => Some(HashType::Md5),
+            40 => Some(HashType::Sha1),
+            56 => Some(HashType::Sha224),
+            64 => Some(HashType::Sha256),
+            96 => Some(HashType::Sha384),
+            128 => Some(HashType::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Length of a hex digest of this hash type.
+    pub(super) fn digest_length(&self) -> usize {
+        match self {
+            HashType::Crc32 => 8,
+            HashType::Md5 => 32,
+            HashType::Sha1 => 40,
+            HashType::Sha224 => 56,
+            HashType::Blake2b256 | HashType::Blake2s256 | HashType::Blake3 | HashType::Sha256 | HashType::Sha3_256 => {
+                64
+            }
+            HashType::Sha384 => 96,
+            HashType::Blake2b512 | HashType::Sha512 | HashType::Sha3_512 => 128,
+        }
+    }
+
+    /// Tag used for this hash type in the BSD-style format.
+    pub(super) fn bsd_tag(&self) -> &'static str {
+        match self {
+            HashType::Blake2b256 => "BLAKE2b-256",
+            HashType::Blake2b512 => "BLAKE2b",
+            HashType::Blake2s256 => "BLAKE2s-256",
+            HashType::Blake3 => "BLAKE3",
+            HashType::Crc32 => "CRC32",
+            HashType::Md5 => "MD5",
+            HashType::Sha1 => "SHA1",
+            HashType::Sha224 => "SHA224",
+            HashType::Sha256 => "SHA256",
+            HashType::Sha384 => "SHA384",
+            HashType::Sha512 => "SHA512",
+            HashType::Sha3_256 => "SHA3-256",
+            HashType::Sha3_512 => "SHA3-512",
+        }
+    }
+
+    pub(super) fn from_bsd_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "BLAKE2b-256" => Some(HashType::Blake2b256),
+            "BLAKE2b" | "BLAKE2b-512" => Some(HashType::Blake2b512),
+            "BLAKE2s-256" => Some(HashType::Blake2s256),
+            "BLAKE3" => Some(HashType::Blake3),
+            "CRC32" => Some(HashType::Crc32),
+            "MD5" => Some(HashType::Md5),
+            "SHA1" => Some(HashType::Sha1),
+            "SHA224" | "SHA2-224" => Some(HashType::Sha224),
+            "SHA256" | "SHA2-256" => Some(HashType::Sha256),
+            "SHA384" | "SHA2-384" => Some(HashType::Sha384),
+            "SHA512" | "SHA2-512" => Some(HashType::Sha512),
+            "SHA3-256" => Some(HashType::Sha3_256),
+            "SHA3-512" => Some(HashType::Sha3_512),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_type_from_filename() {
+        for (filename, hash_type) in [
+            ("SHA256SUMS", HashType::Sha256),
+            ("B2SUMS", HashType::Blake2b512),
+            ("release-1.0-sha256sums.txt", HashType::Sha256),
+            ("sha1sum.txt", HashType::Sha1),
+            ("files.md5", HashType::Md5),
+            ("files.sha3-512", HashType::Sha3_512),
+        ] {
+            assert_eq!(HashType::from_filename(filename), Some(hash_type), "{filename}");
+        }
+
+        for filename in [
+            "md5-to-sha256.txt",
+            "sha1-report.json",
+            "crc32.rs",
+            "checksums",
+            "md5sumsx",
+        ] {
+            assert_eq!(HashType::from_filename(filename), None, "{filename}");
+        }
+    }
+
+    #[test]
+    fn format_from_path() {
+        for (path, format) in [
+            ("files.kecs.json", Some(ChecksumSetFormat::Kecs)),
+            ("release.sfv", Some(ChecksumSetFormat::Sfv)),
+            ("files.hashdeep", Some(ChecksumSetFormat::Hashdeep)),
+            ("SHA512SUMS", Some(ChecksumSetFormat::Gnu)),
+            ("md5-report.out", None),
+        ] {
+            assert_eq!(ChecksumSetFormat::from_path(Path::new(path)), format, "{path}");
+        }
+    }
+
+    #[test]
+    fn detect_format() {
+        let md5 = HashType::Md5.hash_bytes(b"");
+
+        for (content, format) in [
+            ("{\n  \"version\": 1\n}".to_owned(), ChecksumSetFormat::Kecs),
+            (
+                "; Generated by something\na deadbeef\n".to_owned(),
+                ChecksumSetFormat::Sfv,
+            ),
+            ("file name.bin DEADBEEF\n".to_owned(), ChecksumSetFormat::Sfv),
+            (format!("{md5}  a\n"), ChecksumSetFormat::Gnu),
+            (format!("MD5 (a) = {md5}\n"), ChecksumSetFormat::Bsd),
+        ] {
+            assert_eq!(ChecksumSetFormat::detect(content.as_bytes()), format, "{content}");
+        }
+    }
+}
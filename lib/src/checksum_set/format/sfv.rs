@@ -0,0 +1,148 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use tracing::warn;
+
+use crate::checksum_set::{ChecksumSet, FileInfo, HashType};
+
+/// Parse a Simple File Verification (SFV) file.
+///
+/// Each line consists of a file name followed by its CRC32, separated by whitespace.
+/// Lines starting with a semicolon are comments.
+pub(crate) fn parse_sfv_file(content: &str) -> Result<ChecksumSet, String> {
+    let mut files: BTreeMap<String, FileInfo> = BTreeMap::new();
+
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        let (path, hash) =
+            parse_sfv_line(line).ok_or_else(|| format!("Improperly formatted SFV line {}.", line_number + 1))?;
+
+        files.insert(
+            path,
+            FileInfo {
+                size: None,
+                hashes: BTreeMap::from([(HashType::Crc32, hash)]),
+                ..Default::default()
+            },
+        );
+    }
+
+    if files.is_empty() {
+        return Err("No properly formatted SFV lines found.".to_owned());
+    }
+
+    Ok(ChecksumSet {
+        hash_types: vec![HashType::Crc32],
+        files,
+        unreadable_files: Default::default(),
+        scan_paths: Default::default(),
+        filter: Default::default(),
+        metadata: Default::default(),
+        entries: None,
+        follow_symlinks: false,
+    })
+}
+
+/// Write a checksum set as a Simple File Verification (SFV) file.
+pub(crate) fn write_sfv_file<W: Write>(checksums: &ChecksumSet, mut writer: W) -> Result<(), io::Error> {
+    writeln!(writer, "; Generated by KeCS {}", env!("CARGO_PKG_VERSION"))?;
+
+    for (path, fi) in checksums.files.iter() {
+        let Some(hash) = fi.hash(HashType::Crc32) else {
+            continue;
+        };
+
+        if path.contains(['\n', '\r']) {
+            warn!("'{path}' cannot be represented in an SFV file. Skipping.");
+            continue;
+        }
+
+        writeln!(writer, "{path} {}", hash.to_ascii_uppercase())?;
+    }
+
+    writer.flush()
+}
+
+/// Parse a line in SFV format, returning the path and CRC32.
+pub(crate) fn parse_sfv_line(line: &str) -> Option<(String, String)> {
+    let (path, hash) = line.rsplit_once([' ', '\t'])?;
+    let path = path.trim_end();
+
+    if path.is_empty() || hash.len() != 8 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    Some((path.to_owned(), hash.to_ascii_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lines() {
+        let content = "; Comment\n\nfile one.bin\tDEADBEEF\r\ndir/b   0000ffff\n";
+        let checksums = parse_sfv_file(content).unwrap();
+
+        assert_eq!(checksums.hash_types, vec![HashType::Crc32]);
+        assert_eq!(checksums.files.len(), 2);
+        assert_eq!(checksums.files["file one.bin"].hash(HashType::Crc32), Some("deadbeef"));
+        assert_eq!(checksums.files["dir/b"].hash(HashType::Crc32), Some("0000ffff"));
+        assert_eq!(checksums.files["dir/b"].size, None);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_lines() {
+        for line in ["a deadbee", "a deadbeef0", "a deadbeeg", "deadbeef", "a"] {
+            assert_eq!(parse_sfv_line(line), None, "{line}");
+        }
+
+        assert_eq!(
+            parse_sfv_file("a deadbeef\nb 1234\n").unwrap_err(),
+            "Improperly formatted SFV line 2."
+        );
+        assert_eq!(
+            parse_sfv_file("; Only comments\n").unwrap_err(),
+            "No properly formatted SFV lines found."
+        );
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let mut checksums = parse_sfv_file("a b deadbeef\nc 0000ffff\n").unwrap();
+
+        checksums.files.insert(
+            "new\nline".to_owned(),
+            FileInfo {
+                hashes: BTreeMap::from([(HashType::Crc32, "12345678".to_owned())]),
+                ..Default::default()
+            },
+        );
+        checksums.files.insert(
+            "no crc32".to_owned(),
+            FileInfo {
+                hashes: BTreeMap::from([(HashType::Md5, HashType::Md5.hash_bytes(b""))]),
+                ..Default::default()
+            },
+        );
+
+        let mut output = Vec::new();
+        write_sfv_file(&checksums, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("; Generated by KeCS"));
+        assert!(output.ends_with("a b DEADBEEF\nc 0000FFFF\n"));
+
+        let parsed = parse_sfv_file(&output).unwrap();
+
+        assert_eq!(parsed.files.keys().collect::<Vec<_>>(), ["a b", "c"]);
+        assert_eq!(parsed.files["a b"].hash(HashType::Crc32), Some("deadbeef"));
+    }
+}
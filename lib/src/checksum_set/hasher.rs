@@ -1,224 +1,257 @@
-use std::{fs, io::Read, path::Path};
-
-use anyhow::Context;
-use blake2::{Blake2b512, Blake2s256};
+use blake2::{digest::consts::U32, Blake2b, Blake2b512, Blake2s256};
 use byteorder::{BigEndian, ByteOrder};
 use md5::Md5;
 use sha1::Sha1;
-use sha2::{Digest, Sha256};
-use sha3::Sha3_256;
-
-use crate::ui::UiHandler;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use sha3::{Sha3_256, Sha3_512};
+use std::{
+    fmt, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
 
 use super::HashType;
 
 const BUFFER_SIZE: usize = 524288;
 
-impl HashType {
-    pub fn hash<C: FnMut(usize)>(&self, path: &Path, callback: C) -> Result<String, anyhow::Error> {
-        Ok(match self {
-            HashType::Blake2b512 => hash_blake2b512(path, callback)?,
-            HashType::Blake2s256 => hash_blake2s256(path, callback)?,
-            HashType::Blake3 => hash_blake3(path, callback)?,
-            HashType::Crc32 => hash_crc32(path, callback)?,
-            HashType::Md5 => hash_md5(path, callback)?,
-            HashType::Sha1 => hash_sha1(path, callback)?,
-            HashType::Sha256 => hash_sha256(path, callback)?,
-            HashType::Sha3_256 => hash_sha3_256(path, callback)?,
-        })
-    }
-
-    pub fn hash_file(&self, path: &Path, ui: &mut dyn UiHandler) -> Result<String, anyhow::Error> {
-        let filename = path.file_name().unwrap().to_string_lossy();
-        let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
-
-        ui.begin_file(&filename, size);
-
-        let hash = self
-            .hash(path, |b| ui.file_progress(b as u64))
-            .with_context(|| format!("Hashing file: {}", path.display()))?;
-
-        ui.end_file();
+type Blake2b256 = Blake2b<U32>;
 
-        Ok(hash)
-    }
+/// Error preventing a file from being hashed.
+#[derive(Debug)]
+pub enum HashError {
+    Open { path: PathBuf, source: io::Error },
+    Read { path: PathBuf, source: io::Error },
 }
 
-fn hash_blake2b512<C: FnMut(usize)>(path: &Path, mut callback: C) -> Result<String, anyhow::Error> {
-    let mut file = fs::File::open(path).with_context(|| format!("Opening file for hashing: {}", path.display()))?;
-
-    let mut blake2b512 = Blake2b512::new();
-
-    let mut buf = [0u8; BUFFER_SIZE];
-
-    while let Ok(bytes) = file.read(&mut buf) {
-        if bytes == 0 {
-            break;
+impl fmt::Display for HashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Open { path, source } => write!(f, "Opening file for hashing: {}: {source}", path.display()),
+            Self::Read { path, source } => write!(f, "Reading file for hashing: {}: {source}", path.display()),
         }
-
-        blake2b512.update(&buf[..bytes]);
-
-        callback(bytes);
     }
-
-    let hash = blake2b512.finalize();
-
-    Ok(hex::encode(hash))
 }
 
-fn hash_blake2s256<C: FnMut(usize)>(path: &Path, mut callback: C) -> Result<String, anyhow::Error> {
-    let mut file = fs::File::open(path).with_context(|| format!("Opening file for hashing: {}", path.display()))?;
-
-    let mut blake2s256 = Blake2s256::new();
-
-    let mut buf = [0u8; BUFFER_SIZE];
-
-    while let Ok(bytes) = file.read(&mut buf) {
-        if bytes == 0 {
-            break;
+impl std::error::Error for HashError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Open { source, .. } | Self::Read { source, .. } => Some(source),
         }
-
-        blake2s256.update(&buf[..bytes]);
-
-        callback(bytes);
     }
-
-    let hash = blake2s256.finalize();
-
-    Ok(hex::encode(hash))
 }
 
-fn hash_blake3<C: FnMut(usize)>(path: &Path, mut callback: C) -> Result<String, anyhow::Error> {
-    let mut file = fs::File::open(path).with_context(|| format!("Opening file for hashing: {}", path.display()))?;
-
-    let mut hasher = blake3::Hasher::new();
-
-    let mut buf = [0u8; BUFFER_SIZE];
-
-    while let Ok(bytes) = file.read(&mut buf) {
-        if bytes == 0 {
-            break;
-        }
+impl HashType {
+    /// Create an incremental hasher, for hashing data as it becomes available.
+    pub fn hasher(&self) -> Hasher {
+        Hasher::new(*self)
+    }
 
-        hasher.update(&buf[..bytes]);
+    pub fn hash<C: FnMut(usize)>(&self, path: &Path, callback: C) -> Result<String, HashError> {
+        let mut hashes = Self::hash_many(&[*self], path, callback)?;
 
-        callback(bytes);
+        Ok(hashes.remove(0))
     }
 
-    let hash = hasher.finalize();
-
-    Ok(hash.to_hex().to_string())
-}
+    /// Hash a file with several hash types at once, reading it only once.
+    ///
+    /// The returned hashes are in the same order as `hash_types`.
+    pub fn hash_many<C: FnMut(usize)>(
+        hash_types: &[HashType],
+        path: &Path,
+        mut callback: C,
+    ) -> Result<Vec<String>, HashError> {
+        let file = fs::File::open(path).map_err(|source| HashError::Open {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let mut hashers: Vec<Hasher> = hash_types.iter().map(|ht| ht.hasher()).collect();
+
+        read_chunks(file, |chunk| {
+            for hasher in hashers.iter_mut() {
+                hasher.update(chunk);
+            }
+
+            callback(chunk.len());
+        })
+        .map_err(|source| HashError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
 
-fn hash_crc32<C: FnMut(usize)>(path: &Path, mut callback: C) -> Result<String, anyhow::Error> {
-    let mut file = fs::File::open(path).with_context(|| format!("Opening file for hashing: {}", path.display()))?;
+        Ok(hashers.into_iter().map(|h| h.finalize()).collect())
+    }
 
-    let mut hasher = crc32fast::Hasher::new();
+    /// Hash everything read from a reader until the end, such as a network stream or standard input.
+    pub fn hash_reader<R: Read>(&self, reader: R) -> Result<String, io::Error> {
+        let mut hashes = Self::hash_reader_many(&[*self], reader)?;
 
-    let mut buf = [0u8; BUFFER_SIZE];
+        Ok(hashes.remove(0))
+    }
 
-    while let Ok(bytes) = file.read(&mut buf) {
-        if bytes == 0 {
-            break;
-        }
+    /// Hash everything read from a reader with several hash types at once.
+    ///
+    /// The returned hashes are in the same order as `hash_types`.
+    pub fn hash_reader_many<R: Read>(hash_types: &[HashType], reader: R) -> Result<Vec<String>, io::Error> {
+        let mut hashers: Vec<Hasher> = hash_types.iter().map(|ht| ht.hasher()).collect();
 
-        hasher.update(&buf[..bytes]);
+        read_chunks(reader, |chunk| {
+            for hasher in hashers.iter_mut() {
+                hasher.update(chunk);
+            }
+        })?;
 
-        callback(bytes);
+        Ok(hashers.into_iter().map(|h| h.finalize()).collect())
     }
 
-    let hash: u32 = hasher.finalize();
+    /// Hash data already in memory.
+    pub fn hash_bytes(&self, data: &[u8]) -> String {
+        let mut hasher = self.hasher();
 
-    let mut buf = [0u8; 4];
-    BigEndian::write_u32(&mut buf, hash);
+        hasher.update(data);
 
-    Ok(hex::encode(buf))
+        hasher.finalize()
+    }
 }
 
-fn hash_md5<C: FnMut(usize)>(path: &Path, mut callback: C) -> Result<String, anyhow::Error> {
-    let mut file = fs::File::open(path).with_context(|| format!("Opening file for hashing: {}", path.display()))?;
-
-    let mut md5 = Md5::new();
-
-    let mut buf = [0u8; BUFFER_SIZE];
-
-    while let Ok(bytes) = file.read(&mut buf) {
-        if bytes == 0 {
-            break;
-        }
-
-        md5.update(&buf[..bytes]);
-
-        callback(bytes);
+/// Read until the end, passing each chunk read to `f`.
+///
+/// Interrupted reads are retried. Any other read error is returned,
+/// rather than being treated as the end of the data.
+fn read_chunks<R: Read, F: FnMut(&[u8])>(mut reader: R, mut f: F) -> Result<(), io::Error> {
+    let mut buf = vec![0u8; BUFFER_SIZE];
+
+    loop {
+        let bytes = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        f(&buf[..bytes]);
     }
 
-    let hash = md5.finalize();
-
-    Ok(hex::encode(hash))
+    Ok(())
 }
 
-fn hash_sha1<C: FnMut(usize)>(path: &Path, mut callback: C) -> Result<String, anyhow::Error> {
-    let mut file = fs::File::open(path).with_context(|| format!("Opening file for hashing: {}", path.display()))?;
+/// Incremental hasher, producing a hash of all data passed to it in lowercase hexadecimal.
+///
+/// Created using [`HashType::hasher`]. It can also be written to, such as using [`io::copy`].
+pub struct Hasher {
+    state: HasherState,
+}
 
-    let mut sha1 = Sha1::new();
+enum HasherState {
+    Blake2b256(Blake2b256),
+    Blake2b512(Blake2b512),
+    Blake2s256(Blake2s256),
+    Blake3(Box<blake3::Hasher>),
+    Crc32(crc32fast::Hasher),
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha224(Sha224),
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+    Sha3_256(Sha3_256),
+    Sha3_512(Sha3_512),
+}
 
-    let mut buf = [0u8; BUFFER_SIZE];
+impl Hasher {
+    fn new(hash_type: HashType) -> Self {
+        let state = match hash_type {
+            HashType::Blake2b256 => HasherState::Blake2b256(Blake2b256::new()),
+            HashType::Blake2b512 => HasherState::Blake2b512(Blake2b512::new()),
+            HashType::Blake2s256 => HasherState::Blake2s256(Blake2s256::new()),
+            HashType::Blake3 => HasherState::Blake3(Box::new(blake3::Hasher::new())),
+            HashType::Crc32 => HasherState::Crc32(crc32fast::Hasher::new()),
+            HashType::Md5 => HasherState::Md5(Md5::new()),
+            HashType::Sha1 => HasherState::Sha1(Sha1::new()),
+            HashType::Sha224 => HasherState::Sha224(Sha224::new()),
+            HashType::Sha256 => HasherState::Sha256(Sha256::new()),
+            HashType::Sha384 => HasherState::Sha384(Sha384::new()),
+            HashType::Sha512 => HasherState::Sha512(Sha512::new()),
+            HashType::Sha3_256 => HasherState::Sha3_256(Sha3_256::new()),
+            HashType::Sha3_512 => HasherState::Sha3_512(Sha3_512::new()),
+        };
+
+        Self { state }
+    }
 
-    while let Ok(bytes) = file.read(&mut buf) {
-        if bytes == 0 {
-            break;
+    /// Hash type produced by this hasher.
+    pub fn hash_type(&self) -> HashType {
+        match self.state {
+            HasherState::Blake2b256(_) => HashType::Blake2b256,
+            HasherState::Blake2b512(_) => HashType::Blake2b512,
+            HasherState::Blake2s256(_) => HashType::Blake2s256,
+            HasherState::Blake3(_) => HashType::Blake3,
+            HasherState::Crc32(_) => HashType::Crc32,
+            HasherState::Md5(_) => HashType::Md5,
+            HasherState::Sha1(_) => HashType::Sha1,
+            HasherState::Sha224(_) => HashType::Sha224,
+            HasherState::Sha256(_) => HashType::Sha256,
+            HasherState::Sha384(_) => HashType::Sha384,
+            HasherState::Sha512(_) => HashType::Sha512,
+            HasherState::Sha3_256(_) => HashType::Sha3_256,
+            HasherState::Sha3_512(_) => HashType::Sha3_512,
         }
-
-        sha1.update(&buf[..bytes]);
-
-        callback(bytes);
     }
 
-    let hash = sha1.finalize();
-
-    Ok(hex::encode(hash))
-}
-
-fn hash_sha256<C: FnMut(usize)>(path: &Path, mut callback: C) -> Result<String, anyhow::Error> {
-    let mut file = fs::File::open(path).with_context(|| format!("Opening file for hashing: {}", path.display()))?;
-
-    let mut sha256 = Sha256::new();
-
-    let mut buf = [0u8; BUFFER_SIZE];
-
-    while let Ok(bytes) = file.read(&mut buf) {
-        if bytes == 0 {
-            break;
+    /// Add data to the hash.
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.state {
+            HasherState::Blake2b256(h) => h.update(data),
+            HasherState::Blake2b512(h) => h.update(data),
+            HasherState::Blake2s256(h) => h.update(data),
+            HasherState::Blake3(h) => {
+                h.update(data);
+            }
+            HasherState::Crc32(h) => h.update(data),
+            HasherState::Md5(h) => h.update(data),
+            HasherState::Sha1(h) => h.update(data),
+            HasherState::Sha224(h) => h.update(data),
+            HasherState::Sha256(h) => h.update(data),
+            HasherState::Sha384(h) => h.update(data),
+            HasherState::Sha512(h) => h.update(data),
+            HasherState::Sha3_256(h) => h.update(data),
+            HasherState::Sha3_512(h) => h.update(data),
         }
-
-        sha256.update(&buf[..bytes]);
-
-        callback(bytes);
     }
 
-    let hash = sha256.finalize();
-
-    Ok(hex::encode(hash))
-}
-
-fn hash_sha3_256<C: FnMut(usize)>(path: &Path, mut callback: C) -> Result<String, anyhow::Error> {
-    let mut file = fs::File::open(path).with_context(|| format!("Opening file for hashing: {}", path.display()))?;
-
-    let mut sha3_256 = Sha3_256::new();
-
-    let mut buf = [0u8; BUFFER_SIZE];
-
-    while let Ok(bytes) = file.read(&mut buf) {
-        if bytes == 0 {
-            break;
+    /// Finish hashing, returning the hash in lowercase hexadecimal.
+    pub fn finalize(self) -> String {
+        match self.state {
+            HasherState::Blake2b256(h) => hex::encode(h.finalize()),
+            HasherState::Blake2b512(h) => hex::encode(h.finalize()),
+            HasherState::Blake2s256(h) => hex::encode(h.finalize()),
+            HasherState::Blake3(h) => h.finalize().to_hex().to_string(),
+            HasherState::Crc32(h) => {
+                let mut buf = [0u8; 4];
+                BigEndian::write_u32(&mut buf, h.finalize());
+
+                hex::encode(buf)
+            }
+            HasherState::Md5(h) => hex::encode(h.finalize()),
+            HasherState::Sha1(h) => hex::encode(h.finalize()),
+            HasherState::Sha224(h) => hex::encode(h.finalize()),
+            HasherState::Sha256(h) => hex::encode(h.finalize()),
+            HasherState::Sha384(h) => hex::encode(h.finalize()),
+            HasherState::Sha512(h) => hex::encode(h.finalize()),
+            HasherState::Sha3_256(h) => hex::encode(h.finalize()),
+            HasherState::Sha3_512(h) => hex::encode(h.finalize()),
         }
+    }
+}
 
-        sha3_256.update(&buf[..bytes]);
+impl io::Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
 
-        callback(bytes);
+        Ok(buf.len())
     }
 
-    let hash = sha3_256.finalize();
-
-    Ok(hex::encode(hash))
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
@@ -1,22 +1,166 @@
-use std::path::Path;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
 
-use anyhow::Context;
+use serde_derive::Deserialize;
 
-use crate::ui::UiHandler;
+use crate::{ui::UiHandler, Error};
 
-use super::ChecksumSet;
+use super::{format, ChecksumSet, ChecksumSetFormat, Entry, FileFilter, FileInfo, HashType, MetadataKind};
+
+/// On-disk representation of a checksum set.
+///
+/// Older checksum sets only contain a single `hash_type`, with a single
+/// `hash` per file. These are converted to the current representation.
+#[derive(Deserialize)]
+pub(super) struct SerializedChecksumSet {
+    #[serde(default)]
+    hash_type: Option<HashType>,
+    #[serde(default)]
+    hash_types: Vec<HashType>,
+    files: BTreeMap<String, SerializedFileInfo>,
+    #[serde(default)]
+    unreadable_files: BTreeSet<String>,
+    #[serde(default)]
+    scan_paths: Vec<String>,
+    #[serde(default)]
+    filter: FileFilter,
+    #[serde(default)]
+    metadata: BTreeSet<MetadataKind>,
+    #[serde(default)]
+    entries: Option<BTreeMap<String, Entry>>,
+    #[serde(default)]
+    follow_symlinks: bool,
+}
+
+#[derive(Deserialize)]
+struct SerializedFileInfo {
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    hash: Option<String>,
+    #[serde(default)]
+    hashes: BTreeMap<HashType, String>,
+    #[serde(default)]
+    mtime: Option<i64>,
+    #[serde(default)]
+    mode: Option<u32>,
+    #[serde(default)]
+    uid: Option<u32>,
+    #[serde(default)]
+    gid: Option<u32>,
+    #[serde(default)]
+    xattrs: Option<BTreeMap<String, String>>,
+}
+
+impl From<SerializedChecksumSet> for ChecksumSet {
+    fn from(value: SerializedChecksumSet) -> Self {
+        let mut hash_types = value.hash_types;
+
+        if let Some(hash_type) = value.hash_type {
+            if !hash_types.contains(&hash_type) {
+                hash_types.insert(0, hash_type);
+            }
+        }
+
+        let files = value
+            .files
+            .into_iter()
+            .map(|(path, fi)| {
+                let mut hashes = fi.hashes;
+
+                if let (Some(hash_type), Some(hash)) = (value.hash_type, fi.hash) {
+                    hashes.insert(hash_type, hash);
+                }
+
+                (
+                    path,
+                    FileInfo {
+                        size: fi.size,
+                        hashes,
+                        mtime: fi.mtime,
+                        mode: fi.mode,
+                        uid: fi.uid,
+                        gid: fi.gid,
+                        xattrs: fi.xattrs,
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            hash_types,
+            files,
+            unreadable_files: value.unreadable_files,
+            scan_paths: value.scan_paths,
+            filter: value.filter,
+            metadata: value.metadata,
+            entries: value.entries,
+            follow_symlinks: value.follow_symlinks,
+        }
+    }
+}
 
 impl ChecksumSet {
-    pub fn load_from_file(path: &Path, ui: &mut dyn UiHandler) -> Result<Self, anyhow::Error> {
-        let filename = path.file_name().unwrap().to_string_lossy();
+    /// Load a checksum set from a file, detecting its format from its content.
+    pub fn load_from_file(path: &Path, ui: &mut dyn UiHandler) -> Result<Self, Error> {
+        let file = std::fs::File::open(path).map_err(|source| Error::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Self::load_from_reader(file, path, ui)
+    }
+
+    /// Load a checksum set from a reader, such as standard input, detecting its format from its content.
+    ///
+    /// `path` is only used in error messages, and to detect the format or hash type the same way
+    /// as when loading from a file, for formats where they cannot be detected from the content alone.
+    /// It may be `-` for standard input.
+    pub fn load_from_reader<R: Read>(reader: R, path: &Path, ui: &mut dyn UiHandler) -> Result<Self, Error> {
+        let filename = path.file_name().unwrap_or_default().to_string_lossy();
 
         ui.begin_load(&filename);
 
         let checksums = {
-            let file =
-                std::fs::File::open(path).with_context(|| format!("Opening checksum set file: {}", path.display()))?;
+            let read_error = |source| Error::Read {
+                path: path.to_path_buf(),
+                source,
+            };
+
+            let mut reader = BufReader::new(reader);
+
+            let format = match ChecksumSetFormat::from_path(path) {
+                Some(ChecksumSetFormat::Sfv) => ChecksumSetFormat::Sfv,
+                _ => ChecksumSetFormat::detect(reader.fill_buf().map_err(read_error)?),
+            };
+
+            match format {
+                ChecksumSetFormat::Kecs => serde_json::from_reader(reader).map_err(|source| Error::Deserialize {
+                    path: path.to_path_buf(),
+                    source,
+                })?,
+                ChecksumSetFormat::Gnu
+                | ChecksumSetFormat::Bsd
+                | ChecksumSetFormat::Sfv
+                | ChecksumSetFormat::Hashdeep => {
+                    let mut content = String::new();
+
+                    reader.read_to_string(&mut content).map_err(read_error)?;
 
-            serde_json::from_reader(file).with_context(|| "Deserializing checksum set")?
+                    match format {
+                        ChecksumSetFormat::Sfv => format::parse_sfv_file(&content),
+                        ChecksumSetFormat::Hashdeep => format::parse_hashdeep_file(&content),
+                        _ => format::parse_sum_file(&content, HashType::from_filename(&filename)),
+                    }
+                    .map_err(|message| Error::Parse {
+                        path: path.to_path_buf(),
+                        message,
+                    })?
+                }
+            }
         };
 
         ui.end_load();
@@ -0,0 +1,138 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::Metadata,
+    path::Path,
+};
+
+use serde_derive::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+
+use crate::util;
+
+use super::{ChecksumSetDiff, FileInfo};
+
+/// Kind of file metadata that can be stored in a checksum set, and compared.
+#[derive(Copy, Clone, Debug, Deserialize, Display, EnumString, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum MetadataKind {
+    /// Modification time.
+    Mtime,
+    /// Unix permission bits.
+    Mode,
+    /// Unix owner and group IDs.
+    Owner,
+    /// Extended attributes.
+    Xattrs,
+}
+
+/// Unix owner and group IDs of a file.
+pub type Owner = (u32, u32);
+
+impl FileInfo {
+    /// Capture the specified kinds of metadata for a file.
+    ///
+    /// The modification time is not captured, as it is always recorded
+    /// at the time the file is hashed.
+    pub(super) fn capture_metadata(&mut self, path: &Path, metadata: &Metadata, kinds: &BTreeSet<MetadataKind>) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            if kinds.contains(&MetadataKind::Mode) {
+                self.mode = Some(metadata.mode() & 0o7777);
+            }
+
+            if kinds.contains(&MetadataKind::Owner) {
+                self.uid = Some(metadata.uid());
+                self.gid = Some(metadata.gid());
+            }
+
+            if kinds.contains(&MetadataKind::Xattrs) {
+                self.xattrs = read_xattrs(path);
+            }
+        }
+
+        #[cfg(not(unix))]
+        let _ = (path, kinds);
+    }
+
+    /// Read the specified kinds of metadata for a file on disk.
+    pub(super) fn from_metadata(path: &Path, metadata: &Metadata, kinds: &BTreeSet<MetadataKind>) -> Self {
+        let mut fi = FileInfo {
+            size: Some(metadata.len()),
+            mtime: util::modified_nanos(metadata),
+            ..Default::default()
+        };
+
+        fi.capture_metadata(path, metadata, kinds);
+
+        fi
+    }
+}
+
+#[cfg(unix)]
+fn read_xattrs(path: &Path) -> Option<BTreeMap<String, String>> {
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(err) => {
+            tracing::warn!("Reading extended attributes: {}: {err}", path.display());
+            return None;
+        }
+    };
+
+    let xattrs = names
+        .filter_map(|name| {
+            let value = xattr::get(path, &name).ok()??;
+
+            Some((name.to_string_lossy().into_owned(), hex::encode(value)))
+        })
+        .collect();
+
+    Some(xattrs)
+}
+
+/// Compare the specified kinds of metadata of two files, recording any differences in `diff`.
+///
+/// Metadata missing from either file is not compared.
+pub(super) fn compare_metadata(
+    path: &str,
+    a: &FileInfo,
+    b: &FileInfo,
+    kinds: &BTreeSet<MetadataKind>,
+    diff: &mut ChecksumSetDiff,
+) {
+    for kind in kinds {
+        match kind {
+            MetadataKind::Mtime => {
+                if let (Some(a), Some(b)) = (a.mtime, b.mtime) {
+                    if a != b {
+                        diff.differing_mtimes.insert(path.to_owned(), (a, b));
+                    }
+                }
+            }
+            MetadataKind::Mode => {
+                if let (Some(a), Some(b)) = (a.mode, b.mode) {
+                    if a != b {
+                        diff.differing_modes.insert(path.to_owned(), (a, b));
+                    }
+                }
+            }
+            MetadataKind::Owner => {
+                if let (Some(a_uid), Some(a_gid), Some(b_uid), Some(b_gid)) = (a.uid, a.gid, b.uid, b.gid) {
+                    if (a_uid, a_gid) != (b_uid, b_gid) {
+                        diff.differing_owners
+                            .insert(path.to_owned(), ((a_uid, a_gid), (b_uid, b_gid)));
+                    }
+                }
+            }
+            MetadataKind::Xattrs => {
+                if let (Some(a), Some(b)) = (&a.xattrs, &b.xattrs) {
+                    if a != b {
+                        diff.differing_xattrs.insert(path.to_owned());
+                    }
+                }
+            }
+        }
+    }
+}
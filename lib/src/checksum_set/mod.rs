@@ -1,40 +1,149 @@
+mod audit;
 mod builder;
+mod checkpoint;
+mod combine;
 mod diff;
+mod dupes;
+mod entry;
+mod filter;
+mod format;
 mod hasher;
 mod load;
+mod metadata;
+mod pool;
+mod rehash;
+mod report;
+mod scan;
+mod tree;
+mod update;
 mod verify;
 mod write;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use serde_derive::{Deserialize, Serialize};
-use strum_macros::EnumString;
+use strum_macros::{Display, EnumString};
 
+pub use self::audit::*;
 pub use self::builder::*;
+pub use self::checkpoint::CheckpointOptions;
+pub use self::combine::*;
 pub use self::diff::*;
+pub use self::dupes::*;
+pub use self::entry::{Entry, EntryKind};
+pub use self::filter::{FileFilter, IGNORE_FILENAME};
+pub use self::format::{format_sum_line, ChecksumSetFormat};
+pub use self::hasher::{HashError, Hasher};
+pub use self::metadata::{MetadataKind, Owner};
+pub use self::pool::default_thread_count;
+pub use self::rehash::*;
+pub use self::report::{DiffSummary, ReportInfo, REPORT_FORMAT_VERSION};
+pub use self::tree::TreeHash;
+pub use self::update::*;
+pub use self::verify::*;
+pub use self::write::WriteOptions;
 
-#[derive(Copy, Clone, Debug, Deserialize, EnumString, PartialEq, Serialize)]
+#[derive(Copy, Clone, Debug, Deserialize, Display, EnumString, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 #[serde(rename_all = "lowercase")]
 #[strum(serialize_all = "lowercase")]
 pub enum HashType {
+    Blake2b256,
     Blake2b512,
     Blake2s256,
     Blake3,
     Crc32,
     Md5,
     Sha1,
+    Sha224,
     Sha256,
+    Sha384,
+    Sha512,
     Sha3_256,
+    Sha3_512,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct FileInfo {
-    pub size: u64,
-    pub hash: String,
+    /// Size of the file, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    pub hashes: BTreeMap<HashType, String>,
+    /// Modification time, in nanoseconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<i64>,
+    /// Unix permission bits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// Unix owner ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// Unix group ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    /// Extended attributes, with hex-encoded values.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xattrs: Option<BTreeMap<String, String>>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(from = "load::SerializedChecksumSet")]
 pub struct ChecksumSet {
-    pub hash_type: HashType,
+    /// Hash types present for every file, in order of preference.
+    pub hash_types: Vec<HashType>,
     pub files: BTreeMap<String, FileInfo>,
+    /// Files that were found, but could not be read when generating the checksum set.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub unreadable_files: BTreeSet<String>,
+    /// Paths, relative to the root, that were scanned when generating the checksum set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scan_paths: Vec<String>,
+    /// Filter that was applied when scanning for files.
+    #[serde(default, skip_serializing_if = "FileFilter::is_empty")]
+    pub filter: FileFilter,
+    /// Kinds of metadata captured for each file, in addition to size and hashes.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub metadata: BTreeSet<MetadataKind>,
+    /// Symlinks, directories and special files, if recorded when generating the checksum set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entries: Option<BTreeMap<String, Entry>>,
+    /// Whether symlinks were followed rather than recorded when generating the checksum set.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub follow_symlinks: bool,
+}
+
+impl FileInfo {
+    pub fn hash(&self, hash_type: HashType) -> Option<&str> {
+        self.hashes.get(&hash_type).map(|h| h.as_str())
+    }
+}
+
+impl ChecksumSet {
+    /// Hash types present in both this and another checksum set,
+    /// in this set's order of preference.
+    pub fn common_hash_types(&self, other: &ChecksumSet) -> Vec<HashType> {
+        self.hash_types
+            .iter()
+            .filter(|ht| other.hash_types.contains(ht))
+            .copied()
+            .collect()
+    }
+
+    /// Paths, relative to the root, to scan for files that may belong in this checksum set.
+    ///
+    /// Checksum sets that do not record which paths were scanned fall back
+    /// to the top-level entries of the paths they contain.
+    pub fn scan_paths(&self) -> Vec<String> {
+        if !self.scan_paths.is_empty() {
+            return self.scan_paths.clone();
+        }
+
+        let top_level: BTreeSet<&str> = self
+            .files
+            .keys()
+            .chain(self.unreadable_files.iter())
+            .map(|p| p.split('/').next().unwrap_or(p))
+            .collect();
+
+        top_level.into_iter().map(String::from).collect()
+    }
 }
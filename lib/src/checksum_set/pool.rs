@@ -0,0 +1,117 @@
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+use crate::ui::UiHandler;
+
+use super::{HashError, HashType};
+
+/// A file to be hashed by the worker pool.
+pub(crate) struct HashJob<'a> {
+    pub path: &'a Path,
+    pub size: u64,
+}
+
+enum Event {
+    Begin(usize),
+    Progress(usize, u64),
+    End(usize, Result<Vec<String>, HashError>),
+}
+
+/// Number of worker threads used when none is specified.
+pub fn default_thread_count() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Hash all jobs with every one of `hash_types`, using up to `threads` worker threads.
+///
+/// Results are returned in the same order as the jobs, regardless of
+/// which worker finished first. A file that cannot be read does not
+/// abort the other jobs, but yields an error in its place.
+/// All UI events are forwarded from the calling thread, using the job
+/// index as the file id.
+pub(crate) fn hash_files(
+    hash_types: &[HashType],
+    jobs: &[HashJob],
+    threads: usize,
+    ui: &mut dyn UiHandler,
+) -> Vec<Result<BTreeMap<HashType, String>, HashError>> {
+    hash_files_with(hash_types, jobs, threads, ui, |_, _| {})
+}
+
+/// Hash all jobs like [`hash_files`], calling `on_result` from the calling thread
+/// with the index and result of each job as soon as it finishes.
+pub(crate) fn hash_files_with<F>(
+    hash_types: &[HashType],
+    jobs: &[HashJob],
+    threads: usize,
+    ui: &mut dyn UiHandler,
+    mut on_result: F,
+) -> Vec<Result<BTreeMap<HashType, String>, HashError>>
+where
+    F: FnMut(usize, &Result<BTreeMap<HashType, String>, HashError>),
+{
+    let threads = threads.clamp(1, jobs.len().max(1));
+
+    let next_job = AtomicUsize::new(0);
+
+    let mut results: Vec<Option<Result<BTreeMap<HashType, String>, HashError>>> =
+        std::iter::repeat_with(|| None).take(jobs.len()).collect();
+
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel::<Event>();
+
+        for _ in 0..threads {
+            let tx = tx.clone();
+            let next_job = &next_job;
+
+            s.spawn(move || loop {
+                let index = next_job.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = jobs.get(index) else {
+                    break;
+                };
+
+                let _ = tx.send(Event::Begin(index));
+
+                let result = HashType::hash_many(hash_types, job.path, |b| {
+                    let _ = tx.send(Event::Progress(index, b as u64));
+                });
+
+                let _ = tx.send(Event::End(index, result));
+            });
+        }
+
+        // Drop our own sender, so that the receiver loop ends once all workers are done.
+        drop(tx);
+
+        for event in rx {
+            match event {
+                Event::Begin(index) => {
+                    let job = &jobs[index];
+                    let filename = job.path.file_name().unwrap_or_default().to_string_lossy();
+
+                    ui.begin_file(index, &filename, job.size);
+                }
+                Event::Progress(index, bytes) => ui.file_progress(index, bytes),
+                Event::End(index, result) => {
+                    ui.end_file(index);
+
+                    let result = result.map(|hashes| hash_types.iter().copied().zip(hashes).collect());
+
+                    on_result(index, &result);
+
+                    results[index] = Some(result);
+                }
+            }
+        }
+    });
+
+    // Every job is guaranteed to have been processed once all workers have finished.
+    results.into_iter().map(|r| r.unwrap()).collect()
+}
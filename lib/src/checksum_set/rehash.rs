@@ -0,0 +1,295 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde_derive::Serialize;
+use tracing::warn;
+
+use crate::{ui::UiHandler, Error};
+
+use super::{
+    diff,
+    pool::{self, HashJob},
+    ChecksumSet, ChecksumSetDiff, FileInfo, HashType, MetadataKind,
+};
+
+#[derive(Debug)]
+pub struct DiffOptions {
+    /// Number of worker threads used to hash files, if rehashing is necessary.
+    pub threads: usize,
+    /// Root path of the first checksum set's files.
+    pub root_path_a: Option<PathBuf>,
+    /// Root path of the second checksum set's files.
+    pub root_path_b: Option<PathBuf>,
+    /// Kinds of metadata not to compare, even if captured in both checksum sets.
+    pub ignore_metadata: BTreeSet<MetadataKind>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            threads: pool::default_thread_count(),
+            root_path_a: None,
+            root_path_b: None,
+            ignore_metadata: BTreeSet::new(),
+        }
+    }
+}
+
+/// Side of a comparison.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffSide {
+    A,
+    B,
+}
+
+/// Details of files being rehashed to compare checksum sets with no hash types in common.
+#[derive(Debug, Serialize)]
+pub struct RehashInfo {
+    /// Side whose files were rehashed.
+    pub side: DiffSide,
+    /// Hash types the files were rehashed with.
+    pub hash_types: Vec<HashType>,
+    /// Files whose current contents no longer match the checksum set being rehashed.
+    /// These could not be compared.
+    pub changed_files: BTreeSet<String>,
+}
+
+impl ChecksumSet {
+    /// Compare with another checksum set.
+    ///
+    /// If the checksum sets have no hash types in common and the root path of either
+    /// one's files is specified, those files are rehashed using the other checksum set's
+    /// hash types to make the comparison possible. If both are specified, the second
+    /// checksum set's files are rehashed.
+    pub fn diff_with_options(
+        &self,
+        other: &ChecksumSet,
+        options: &DiffOptions,
+        ui: &mut dyn UiHandler,
+    ) -> Result<ChecksumSetDiff, Error> {
+        if !self.common_hash_types(other).is_empty() {
+            return self.diff_ignoring_metadata(other, &options.ignore_metadata, ui);
+        }
+
+        let (side, root_path) = match (&options.root_path_a, &options.root_path_b) {
+            (_, Some(root_path)) => (DiffSide::B, root_path),
+            (Some(root_path), None) => (DiffSide::A, root_path),
+            (None, None) => return Err(Error::RehashRootRequired),
+        };
+
+        let (rehashed, changed_files) = match side {
+            DiffSide::A => self.rehash(root_path, &other.hash_types, options.threads, ui)?,
+            DiffSide::B => other.rehash(root_path, &self.hash_types, options.threads, ui)?,
+        };
+
+        let mut diff = match side {
+            DiffSide::A => rehashed.diff_ignoring_metadata(other, &options.ignore_metadata, ui)?,
+            DiffSide::B => self.diff_ignoring_metadata(&rehashed, &options.ignore_metadata, ui)?,
+        };
+
+        diff.unreadable_files.retain(|p| !changed_files.contains(p));
+
+        diff.rehash = Some(RehashInfo {
+            side,
+            hash_types: rehashed.hash_types,
+            changed_files,
+        });
+
+        Ok(diff)
+    }
+
+    /// Rehash the files of this checksum set using different hash types.
+    ///
+    /// Files are also hashed using the checksum set's own hash types, to ensure that they
+    /// have not changed since it was generated. Returns the rehashed checksum set,
+    /// along with the files that have changed. Changed files are treated as unreadable
+    /// in the rehashed checksum set, as they cannot be meaningfully compared.
+    /// Files that no longer exist are left out of it, so that they are reported as missing.
+    ///
+    /// The metadata recorded for each file is kept as is, as only its hashes are replaced.
+    fn rehash(
+        &self,
+        root_path: &Path,
+        hash_types: &[HashType],
+        threads: usize,
+        ui: &mut dyn UiHandler,
+    ) -> Result<(ChecksumSet, BTreeSet<String>), Error> {
+        ui.begin_prepare();
+
+        let files: Vec<(&String, &FileInfo, PathBuf, u64)> = self
+            .files
+            .iter()
+            .filter_map(|(path, fi)| {
+                let actual_path = root_path.join(path);
+
+                // Files that cannot be read for any other reason are still hashed, to report the error.
+                let size = match fs::metadata(&actual_path) {
+                    Ok(metadata) => metadata.len(),
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+                    Err(_) => 0,
+                };
+
+                Some((path, fi, actual_path, size))
+            })
+            .collect();
+
+        let jobs: Vec<HashJob> = files
+            .iter()
+            .map(|(_, _, actual_path, size)| HashJob {
+                path: actual_path,
+                size: *size,
+            })
+            .collect();
+
+        let total_size: u64 = jobs.iter().map(|job| job.size).sum();
+
+        let all_hash_types: Vec<HashType> = hash_types
+            .iter()
+            .chain(self.hash_types.iter())
+            .copied()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        ui.end_prepare();
+
+        ui.begin_generate(jobs.len() as u32, total_size);
+
+        let results = pool::hash_files(&all_hash_types, &jobs, threads, ui);
+
+        ui.end_generate();
+
+        let mut rehashed = ChecksumSet {
+            hash_types: hash_types.to_vec(),
+            files: BTreeMap::new(),
+            unreadable_files: self.unreadable_files.clone(),
+            scan_paths: self.scan_paths.clone(),
+            filter: self.filter.clone(),
+            metadata: self.metadata.clone(),
+            entries: self.entries.clone(),
+            follow_symlinks: self.follow_symlinks,
+        };
+
+        let mut changed_files: BTreeSet<String> = BTreeSet::new();
+
+        for ((path, fi, _, size), result) in files.into_iter().zip(results) {
+            let mut hashes = match result {
+                Ok(hashes) => hashes,
+                Err(err) => {
+                    warn!("{err}");
+                    rehashed.unreadable_files.insert(path.clone());
+                    continue;
+                }
+            };
+
+            if fi.size.is_some_and(|s| s != size)
+                || diff::compare_hashes(&fi.hashes, &hashes, &self.hash_types).is_some()
+            {
+                rehashed.unreadable_files.insert(path.clone());
+                changed_files.insert(path.clone());
+                continue;
+            }
+
+            hashes.retain(|ht, _| hash_types.contains(ht));
+
+            rehashed.files.insert(
+                path.clone(),
+                FileInfo {
+                    size: Some(size),
+                    hashes,
+                    mtime: fi.mtime,
+                    mode: fi.mode,
+                    uid: fi.uid,
+                    gid: fi.gid,
+                    xattrs: fi.xattrs.clone(),
+                },
+            );
+        }
+
+        Ok((rehashed, changed_files))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        checksum_set::ChecksumSetBuilder,
+        test_util::{NullUi, TempDir},
+    };
+
+    use super::*;
+
+    fn generate(dir: &TempDir, hash_type: HashType) -> ChecksumSet {
+        ChecksumSetBuilder::new(&[hash_type], dir.path())
+            .add_path(dir.path(), &mut NullUi)
+            .build(&mut NullUi)
+            .unwrap()
+    }
+
+    fn diff_rehashing(a: &ChecksumSet, b: &ChecksumSet, side: DiffSide, root_path: &Path) -> ChecksumSetDiff {
+        let options = match side {
+            DiffSide::A => DiffOptions {
+                root_path_a: Some(root_path.to_path_buf()),
+                ..Default::default()
+            },
+            DiffSide::B => DiffOptions {
+                root_path_b: Some(root_path.to_path_buf()),
+                ..Default::default()
+            },
+        };
+
+        a.diff_with_options(b, &options, &mut NullUi).unwrap()
+    }
+
+    #[test]
+    fn rehash_notice_without_differences() {
+        let dir = TempDir::new();
+        dir.write("a", "a");
+        dir.write("sub/b", "b");
+
+        let a = generate(&dir, HashType::Md5);
+        let b = generate(&dir, HashType::Sha1);
+
+        let diff = diff_rehashing(&a, &b, DiffSide::B, dir.path());
+
+        assert!(!diff.is_different());
+        assert_eq!(
+            diff.rehash_notice().as_deref(),
+            Some("Checksum sets have no hash types in common. Files of B were rehashed using md5.")
+        );
+    }
+
+    #[test]
+    fn no_rehash_notice_with_common_hash_types() {
+        let dir = TempDir::new();
+        dir.write("a", "a");
+
+        let a = generate(&dir, HashType::Md5);
+        let b = generate(&dir, HashType::Md5);
+
+        assert_eq!(diff_rehashing(&a, &b, DiffSide::B, dir.path()).rehash_notice(), None);
+    }
+
+    #[test]
+    fn rehash_reports_deleted_files_as_missing() {
+        let dir = TempDir::new();
+        dir.write("a", "a");
+        dir.write("b", "b");
+
+        let a = generate(&dir, HashType::Md5);
+        let b = generate(&dir, HashType::Sha1);
+
+        fs::remove_file(dir.path().join("b")).unwrap();
+
+        let diff = diff_rehashing(&a, &b, DiffSide::A, dir.path());
+
+        assert_eq!(diff.missing_files, BTreeSet::from(["b".to_owned()]));
+        assert!(diff.unreadable_files.is_empty());
+    }
+}
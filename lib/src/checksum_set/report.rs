@@ -0,0 +1,236 @@
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use serde_derive::Serialize;
+
+use super::{ChecksumSetDiff, EntryKind, HashType, Owner, RehashInfo};
+
+/// Version of the machine-readable report format.
+///
+/// This is increased whenever a field is removed or its meaning changes.
+/// Adding fields is not considered a breaking change.
+pub const REPORT_FORMAT_VERSION: u32 = 1;
+
+/// Information about the operation that produced a diff, included in machine-readable reports.
+#[derive(Debug, Serialize)]
+pub struct ReportInfo {
+    /// Operation that produced the diff, such as `diff` or `verify`.
+    pub operation: String,
+    /// Checksum set files involved in the operation.
+    pub checksum_sets: Vec<String>,
+    /// Root path files were verified against, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_path: Option<String>,
+    /// Time taken by the operation.
+    #[serde(rename = "duration_secs", serialize_with = "serialize_duration")]
+    pub duration: Duration,
+}
+
+/// Number of files in each category of a diff.
+#[derive(Debug, Serialize)]
+pub struct DiffSummary {
+    pub different: bool,
+    pub additional_files: usize,
+    pub missing_files: usize,
+    pub moved_files: usize,
+    pub differing_sizes: usize,
+    pub differing_hashes: usize,
+    pub unreadable_files: usize,
+    pub changed_files: usize,
+    pub differing_mtimes: usize,
+    pub differing_modes: usize,
+    pub differing_owners: usize,
+    pub differing_xattrs: usize,
+    pub additional_entries: usize,
+    pub missing_entries: usize,
+    pub differing_kinds: usize,
+    pub differing_link_targets: usize,
+}
+
+/// Complete JSON report of a diff.
+#[derive(Serialize)]
+struct DiffReport<'a> {
+    format_version: u32,
+    tool_version: &'static str,
+    #[serde(flatten)]
+    info: &'a ReportInfo,
+    summary: DiffSummary,
+    #[serde(flatten)]
+    diff: &'a ChecksumSetDiff,
+}
+
+/// Line of an NDJSON report of a diff.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum DiffReportLine<'a> {
+    File(DiffEntry<'a>),
+    Summary {
+        format_version: u32,
+        tool_version: &'static str,
+        #[serde(flatten)]
+        info: &'a ReportInfo,
+        hash_types: &'a [HashType],
+        #[serde(skip_serializing_if = "Option::is_none")]
+        rehash: Option<&'a RehashInfo>,
+        summary: DiffSummary,
+    },
+}
+
+/// Single file in an NDJSON report of a diff.
+///
+/// For differing sizes, hashes and metadata, `a` is the value in the first checksum set
+/// (or the expected value when verifying) and `b` the value in the second one
+/// (or the actual value when verifying).
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum DiffEntry<'a> {
+    Additional { path: &'a str },
+    Missing { path: &'a str },
+    Moved { path: &'a str, new_path: &'a str },
+    DifferingSize { path: &'a str, a: u64, b: u64 },
+    DifferingHash { path: &'a str, a: &'a str, b: &'a str },
+    Unreadable { path: &'a str },
+    Changed { path: &'a str },
+    DifferingMtime { path: &'a str, a: i64, b: i64 },
+    DifferingMode { path: &'a str, a: u32, b: u32 },
+    DifferingOwner { path: &'a str, a: Owner, b: Owner },
+    DifferingXattrs { path: &'a str },
+    AdditionalEntry { path: &'a str, kind: EntryKind },
+    MissingEntry { path: &'a str, kind: EntryKind },
+    DifferingKind { path: &'a str, a: EntryKind, b: EntryKind },
+    DifferingLinkTarget { path: &'a str, a: &'a str, b: &'a str },
+}
+
+impl ChecksumSetDiff {
+    pub fn summary(&self) -> DiffSummary {
+        DiffSummary {
+            different: self.is_different(),
+            additional_files: self.additional_files.len(),
+            missing_files: self.missing_files.len(),
+            moved_files: self.moved_files.len(),
+            differing_sizes: self.differing_sizes.len(),
+            differing_hashes: self.differing_hashes.len(),
+            unreadable_files: self.unreadable_files.len(),
+            changed_files: self.rehash.as_ref().map_or(0, |r| r.changed_files.len()),
+            differing_mtimes: self.differing_mtimes.len(),
+            differing_modes: self.differing_modes.len(),
+            differing_owners: self.differing_owners.len(),
+            differing_xattrs: self.differing_xattrs.len(),
+            additional_entries: self.additional_entries.len(),
+            missing_entries: self.missing_entries.len(),
+            differing_kinds: self.differing_kinds.len(),
+            differing_link_targets: self.differing_link_targets.len(),
+        }
+    }
+
+    /// Write the diff as a single JSON document.
+    pub fn write_json<W: Write>(&self, info: &ReportInfo, mut writer: W) -> Result<(), io::Error> {
+        let report = DiffReport {
+            format_version: REPORT_FORMAT_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION"),
+            info,
+            summary: self.summary(),
+            diff: self,
+        };
+
+        serde_json::to_writer_pretty(&mut writer, &report)?;
+        writeln!(writer)?;
+
+        writer.flush()
+    }
+
+    /// Write the diff as newline-delimited JSON, with one line per file
+    /// followed by a summary line.
+    pub fn write_ndjson<W: Write>(&self, info: &ReportInfo, mut writer: W) -> Result<(), io::Error> {
+        let entries = self
+            .additional_files
+            .iter()
+            .map(|p| DiffEntry::Additional { path: p })
+            .chain(self.missing_files.iter().map(|p| DiffEntry::Missing { path: p }))
+            .chain(self.moved_files.iter().map(|(from, to)| DiffEntry::Moved {
+                path: from,
+                new_path: to,
+            }))
+            .chain(
+                self.differing_sizes
+                    .iter()
+                    .map(|(p, (a, b))| DiffEntry::DifferingSize { path: p, a: *a, b: *b }),
+            )
+            .chain(
+                self.differing_hashes
+                    .iter()
+                    .map(|(p, (a, b))| DiffEntry::DifferingHash { path: p, a, b }),
+            )
+            .chain(self.unreadable_files.iter().map(|p| DiffEntry::Unreadable { path: p }))
+            .chain(
+                self.rehash
+                    .iter()
+                    .flat_map(|r| r.changed_files.iter().map(|p| DiffEntry::Changed { path: p })),
+            )
+            .chain(
+                self.differing_mtimes
+                    .iter()
+                    .map(|(p, (a, b))| DiffEntry::DifferingMtime { path: p, a: *a, b: *b }),
+            )
+            .chain(
+                self.differing_modes
+                    .iter()
+                    .map(|(p, (a, b))| DiffEntry::DifferingMode { path: p, a: *a, b: *b }),
+            )
+            .chain(
+                self.differing_owners
+                    .iter()
+                    .map(|(p, (a, b))| DiffEntry::DifferingOwner { path: p, a: *a, b: *b }),
+            )
+            .chain(
+                self.differing_xattrs
+                    .iter()
+                    .map(|p| DiffEntry::DifferingXattrs { path: p }),
+            )
+            .chain(
+                self.additional_entries
+                    .iter()
+                    .map(|(p, kind)| DiffEntry::AdditionalEntry { path: p, kind: *kind }),
+            )
+            .chain(
+                self.missing_entries
+                    .iter()
+                    .map(|(p, kind)| DiffEntry::MissingEntry { path: p, kind: *kind }),
+            )
+            .chain(
+                self.differing_kinds
+                    .iter()
+                    .map(|(p, (a, b))| DiffEntry::DifferingKind { path: p, a: *a, b: *b }),
+            )
+            .chain(
+                self.differing_link_targets
+                    .iter()
+                    .map(|(p, (a, b))| DiffEntry::DifferingLinkTarget { path: p, a, b }),
+            );
+
+        for entry in entries {
+            serde_json::to_writer(&mut writer, &DiffReportLine::File(entry))?;
+            writeln!(writer)?;
+        }
+
+        let summary = DiffReportLine::Summary {
+            format_version: REPORT_FORMAT_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION"),
+            info,
+            hash_types: &self.hash_types,
+            rehash: self.rehash.as_ref(),
+            summary: self.summary(),
+        };
+
+        serde_json::to_writer(&mut writer, &summary)?;
+        writeln!(writer)?;
+
+        writer.flush()
+    }
+}
+
+fn serialize_duration<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
@@ -0,0 +1,136 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::util;
+
+use super::{
+    filter::{FilterMatcher, IGNORE_FILENAME},
+    Entry,
+};
+
+/// Files and other entries found by scanning, relative to a root path.
+#[derive(Debug, Default)]
+pub(super) struct ScannedPaths {
+    /// Regular files, mapped to their actual path.
+    pub files: BTreeMap<String, PathBuf>,
+    /// Symlinks, directories and special files.
+    pub entries: BTreeMap<String, Entry>,
+}
+
+/// Recursively find all entries under a path, skipping any excluded by the filter.
+///
+/// Regular files are returned without an entry. Symlinks are only returned as entries
+/// if not following them, and the path being scanned is itself only returned if it is
+/// not the root path.
+///
+/// Paths are matched against the filter relative to the root path.
+pub(super) fn scan_entries(
+    path: &Path,
+    root_path: &Path,
+    filter: &Arc<FilterMatcher>,
+    follow_symlinks: bool,
+) -> Vec<(PathBuf, Option<Entry>)> {
+    let entry_root_path = root_path.to_path_buf();
+    let entry_filter = filter.clone();
+
+    ignore::WalkBuilder::new(path)
+        .standard_filters(false)
+        .parents(false)
+        .follow_links(follow_symlinks)
+        .add_custom_ignore_filename(IGNORE_FILENAME)
+        .filter_entry(move |entry| {
+            if entry.depth() == 0 {
+                return true;
+            }
+
+            let Ok(rel_path) = entry.path().strip_prefix(&entry_root_path) else {
+                return true;
+            };
+
+            let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+
+            !entry_filter.is_excluded(rel_path, is_dir)
+        })
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_type = entry.file_type()?;
+
+            if file_type.is_file() {
+                return Some((entry.into_path(), None));
+            }
+
+            if entry.path() == root_path {
+                return None;
+            }
+
+            let scanned_entry = Entry::from_file_type(entry.path(), file_type)?;
+
+            Some((entry.into_path(), Some(scanned_entry)))
+        })
+        .filter(|(path, _)| {
+            path.strip_prefix(root_path)
+                .map_or(true, |rel_path| filter.is_included(rel_path))
+        })
+        .collect()
+}
+
+/// Recursively find all files and other entries under the specified paths, relative to a root path.
+pub(super) fn scan_relative(
+    root_path: &Path,
+    scan_paths: &[String],
+    filter: &Arc<FilterMatcher>,
+    follow_symlinks: bool,
+) -> ScannedPaths {
+    let mut scanned = ScannedPaths::default();
+
+    for scan_path in scan_paths {
+        for (path, entry) in scan_entries(&root_path.join(scan_path), root_path, filter, follow_symlinks) {
+            let Ok(rel_path) = path.strip_prefix(root_path) else {
+                continue;
+            };
+
+            let rel_path = util::unixify_path(rel_path);
+
+            match entry {
+                Some(entry) => {
+                    scanned.entries.insert(rel_path, entry);
+                }
+                None => {
+                    scanned.files.insert(rel_path, path);
+                }
+            }
+        }
+    }
+
+    scanned
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn scan_honors_kecsignore_files_only() {
+        let dir = TempDir::new();
+        dir.write("a.tmp", "");
+        dir.write("b.log", "");
+        dir.write("sub/c.tmp", "");
+        dir.write("sub/d.log", "");
+        dir.write(".gitignore", "*.log\n");
+        dir.write(&format!("sub/{IGNORE_FILENAME}"), "*.tmp\n");
+
+        let scanned = scan_relative(dir.path(), &[String::new()], &Arc::new(FilterMatcher::default()), false);
+
+        assert_eq!(
+            scanned.files.keys().collect::<Vec<_>>(),
+            [".gitignore", "a.tmp", "b.log", "sub/.kecsignore", "sub/d.log"]
+        );
+        assert_eq!(scanned.entries.keys().collect::<Vec<_>>(), ["sub"]);
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use crate::Error;
+
+use super::{ChecksumSet, HashType};
+
+/// Tree hash of a checksum set, summarizing all of its files in a single hash.
+///
+/// Each directory is hashed by hashing the concatenation of a record for each of its
+/// children, sorted by name, in byte order. Files are recorded as
+/// `file <size> <hash> <name>\0`, and subdirectories as `dir <subtree hash> <name>\0`,
+/// where sizes are in decimal, hashes are in lowercase hexadecimal, and names are the
+/// last component of the path. The root hash is the hash of the top-level directory.
+///
+/// All hashing is done using the same hash type as the file hashes being summarized.
+/// Only files are included. Unreadable files, symlinks, directories without any
+/// files in them and metadata do not affect the tree hash.
+#[derive(Debug)]
+pub struct TreeHash {
+    /// Hash type used to hash the files and the tree.
+    pub hash_type: HashType,
+    /// Hash of the entire tree.
+    pub root: String,
+    /// Subtree hashes of each directory containing files, keyed by path.
+    /// The top-level directory has an empty path.
+    pub directories: BTreeMap<String, String>,
+}
+
+#[derive(Default)]
+struct Directory<'a> {
+    files: BTreeMap<&'a str, (u64, &'a str)>,
+    directories: BTreeMap<&'a str, Directory<'a>>,
+}
+
+impl ChecksumSet {
+    /// Compute the tree hash of the checksum set.
+    ///
+    /// If `hash_type` is not specified, the checksum set's preferred hash type is used.
+    /// Every file must have a known size and a hash of the hash type used.
+    pub fn tree_hash(&self, hash_type: Option<HashType>) -> Result<TreeHash, Error> {
+        let hash_type = match hash_type {
+            Some(hash_type) if !self.hash_types.contains(&hash_type) => {
+                return Err(Error::MissingHashType(hash_type));
+            }
+            Some(hash_type) => hash_type,
+            None => *self.hash_types.first().ok_or(Error::NoHashTypes)?,
+        };
+
+        let mut root = Directory::default();
+
+        for (path, fi) in self.files.iter() {
+            let Some(size) = fi.size else {
+                return Err(Error::UnknownSize { path: path.clone() });
+            };
+
+            let Some(hash) = fi.hash(hash_type) else {
+                return Err(Error::MissingHash {
+                    path: path.clone(),
+                    hash_type,
+                });
+            };
+
+            let (dir_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+
+            let mut directory = &mut root;
+
+            for component in dir_path.split('/').filter(|c| !c.is_empty()) {
+                directory = directory.directories.entry(component).or_default();
+            }
+
+            directory.files.insert(name, (size, hash));
+        }
+
+        let mut directories: BTreeMap<String, String> = BTreeMap::new();
+
+        let root = hash_directory(&root, "", hash_type, &mut directories)?;
+
+        Ok(TreeHash {
+            hash_type,
+            root,
+            directories,
+        })
+    }
+}
+
+/// Hash a directory, recording its subtree hash and that of all of its subdirectories in `directories`.
+fn hash_directory(
+    directory: &Directory,
+    path: &str,
+    hash_type: HashType,
+    directories: &mut BTreeMap<String, String>,
+) -> Result<String, Error> {
+    let mut records: BTreeMap<&str, String> = BTreeMap::new();
+
+    for (name, (size, hash)) in directory.files.iter() {
+        records.insert(name, format!("file {size} {} {name}\0", hash.to_lowercase()));
+    }
+
+    for (name, subdirectory) in directory.directories.iter() {
+        let subdirectory_path = if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{path}/{name}")
+        };
+
+        if records.contains_key(name) {
+            return Err(Error::PathConflict {
+                path: subdirectory_path,
+            });
+        }
+
+        let subtree_hash = hash_directory(subdirectory, &subdirectory_path, hash_type, directories)?;
+
+        records.insert(name, format!("dir {subtree_hash} {name}\0"));
+    }
+
+    let data: String = records.into_values().collect();
+    let hash = hash_type.hash_bytes(data.as_bytes());
+
+    directories.insert(path.to_owned(), hash.clone());
+
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::checksum_set;
+
+    use super::*;
+
+    #[test]
+    fn tree_hash_records() {
+        let checksums = checksum_set(&[HashType::Md5], &[("a", "x"), ("d/e/b", "yz")]);
+        let tree = checksums.tree_hash(None).unwrap();
+
+        let md5 = |data: &str| HashType::Md5.hash_bytes(data.as_bytes());
+        let e = md5(&format!("file 2 {} b\0", md5("yz")));
+        let d = md5(&format!("dir {e} e\0"));
+        let root = md5(&format!("file 1 {} a\0dir {d} d\0", md5("x")));
+
+        assert_eq!(tree.hash_type, HashType::Md5);
+        assert_eq!(tree.root, root);
+        assert_eq!(
+            tree.directories,
+            BTreeMap::from([("".to_owned(), root), ("d".to_owned(), d), ("d/e".to_owned(), e)])
+        );
+    }
+
+    #[test]
+    fn tree_hash_changes() {
+        let hash = |files: &[(&str, &str)]| checksum_set(&[HashType::Sha256], files).tree_hash(None).unwrap();
+
+        let tree = hash(&[("a/b", "1"), ("c/d", "2")]);
+        let renamed = hash(&[("a/b", "1"), ("c/e", "2")]);
+        let modified = hash(&[("a/b", "1"), ("c/d", "3")]);
+
+        assert_ne!(tree.root, renamed.root);
+        assert_ne!(tree.root, modified.root);
+        assert_eq!(tree.directories["a"], renamed.directories["a"]);
+        assert_ne!(tree.directories["c"], modified.directories["c"]);
+    }
+
+    #[test]
+    fn tree_hash_errors() {
+        let checksums = checksum_set(&[HashType::Md5], &[("a", "x"), ("a/b", "y")]);
+
+        assert!(matches!(
+            checksums.tree_hash(None),
+            Err(Error::PathConflict { path }) if path == "a"
+        ));
+        assert!(matches!(
+            checksums.tree_hash(Some(HashType::Sha1)),
+            Err(Error::MissingHashType(HashType::Sha1))
+        ));
+
+        let mut checksums = checksum_set(&[HashType::Md5], &[("a", "x")]);
+        checksums.files.get_mut("a").unwrap().size = None;
+
+        assert!(matches!(checksums.tree_hash(None), Err(Error::UnknownSize { path }) if path == "a"));
+
+        let mut checksums = checksum_set(&[HashType::Md5], &[("a", "x")]);
+        checksums.hash_types.push(HashType::Sha1);
+
+        assert!(matches!(
+            checksums.tree_hash(Some(HashType::Sha1)),
+            Err(Error::MissingHash { path, hash_type: HashType::Sha1 }) if path == "a"
+        ));
+    }
+}
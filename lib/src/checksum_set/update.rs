@@ -0,0 +1,263 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tracing::warn;
+
+use crate::{ui::UiHandler, util, Error};
+
+use super::{
+    pool::{self, HashJob},
+    scan, ChecksumSet, FileInfo,
+};
+
+#[derive(Debug)]
+pub struct UpdateOptions {
+    /// Number of worker threads used to hash files.
+    pub threads: usize,
+}
+
+impl Default for UpdateOptions {
+    fn default() -> Self {
+        Self {
+            threads: pool::default_thread_count(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ChecksumSetUpdate {
+    pub added_files: BTreeSet<String>,
+    pub modified_files: BTreeSet<String>,
+    pub removed_files: BTreeSet<String>,
+    pub unreadable_files: BTreeSet<String>,
+}
+
+impl ChecksumSet {
+    /// Update the checksum set to match the current contents of the root path.
+    ///
+    /// Files whose size and modification time are unchanged are assumed to be
+    /// unchanged, and are not rehashed. Checksum sets loaded from other formats
+    /// do not record modification times, so all of their files are rehashed.
+    pub fn update(
+        &mut self,
+        root_path: &Path,
+        options: &UpdateOptions,
+        ui: &mut dyn UiHandler,
+    ) -> Result<ChecksumSetUpdate, Error> {
+        let filter = Arc::new(self.filter.compile()?);
+
+        ui.begin_scan();
+
+        let scanned = scan::scan_relative(root_path, &self.scan_paths(), &filter, self.follow_symlinks);
+        let found_files = scanned.files;
+
+        ui.end_scan();
+
+        ui.begin_prepare();
+
+        let mut update = ChecksumSetUpdate {
+            removed_files: self
+                .files
+                .keys()
+                .chain(self.unreadable_files.iter())
+                .filter(|p| !found_files.contains_key(*p))
+                .cloned()
+                .collect(),
+            ..Default::default()
+        };
+
+        let mut to_hash: Vec<(String, u64, Option<i64>)> = Vec::new();
+        let mut actual_paths: Vec<PathBuf> = Vec::new();
+
+        for (rel_path, file_path) in found_files {
+            let metadata = std::fs::metadata(&file_path).ok();
+            let size = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
+            let mtime = metadata.as_ref().and_then(util::modified_nanos);
+
+            if let Some(fi) = self.files.get_mut(&rel_path) {
+                if fi.size == Some(size) && fi.mtime.is_some() && fi.mtime == mtime {
+                    // Other metadata may change without the modification time changing.
+                    if let Some(metadata) = &metadata {
+                        fi.capture_metadata(&file_path, metadata, &self.metadata);
+                    }
+
+                    continue;
+                }
+            }
+
+            to_hash.push((rel_path, size, mtime));
+            actual_paths.push(file_path);
+        }
+
+        let jobs: Vec<HashJob> = actual_paths
+            .iter()
+            .zip(to_hash.iter())
+            .map(|(path, (_, size, _))| HashJob { path, size: *size })
+            .collect();
+
+        let total_size: u64 = jobs.iter().map(|job| job.size).sum();
+
+        ui.end_prepare();
+
+        for path in update.removed_files.iter() {
+            self.files.remove(path);
+            self.unreadable_files.remove(path);
+        }
+
+        ui.begin_generate(jobs.len() as u32, total_size);
+
+        let results = pool::hash_files(&self.hash_types, &jobs, options.threads, ui);
+
+        for (((rel_path, size, mtime), result), file_path) in to_hash.into_iter().zip(results).zip(actual_paths.iter())
+        {
+            let hashes: BTreeMap<_, _> = match result {
+                Ok(hashes) => hashes,
+                Err(err) => {
+                    warn!("{err}");
+
+                    // Keep any previous entry, as it may still be correct.
+                    if !self.files.contains_key(&rel_path) {
+                        self.unreadable_files.insert(rel_path.clone());
+                    }
+
+                    update.unreadable_files.insert(rel_path);
+                    continue;
+                }
+            };
+
+            self.unreadable_files.remove(&rel_path);
+
+            let mut fi = FileInfo {
+                size: Some(size),
+                hashes,
+                mtime,
+                ..Default::default()
+            };
+
+            if !self.metadata.is_empty() {
+                if let Ok(metadata) = std::fs::metadata(file_path) {
+                    fi.capture_metadata(file_path, &metadata, &self.metadata);
+                }
+            }
+
+            let previous = self.files.insert(rel_path.clone(), fi);
+
+            match previous {
+                Some(previous) => {
+                    if previous.size != Some(size) || previous.hashes != self.files[&rel_path].hashes {
+                        update.modified_files.insert(rel_path);
+                    }
+                }
+                None => {
+                    update.added_files.insert(rel_path);
+                }
+            }
+        }
+
+        ui.end_generate();
+
+        if let Some(entries) = self.entries.as_mut() {
+            for (path, entry) in entries.iter() {
+                match scanned.entries.get(path) {
+                    Some(found_entry) if found_entry != entry => {
+                        update.modified_files.insert(path.clone());
+                    }
+                    Some(_) => {}
+                    None => {
+                        update.removed_files.insert(path.clone());
+                    }
+                }
+            }
+
+            for path in scanned.entries.keys() {
+                if !entries.contains_key(path) {
+                    update.added_files.insert(path.clone());
+                }
+            }
+
+            *entries = scanned.entries;
+
+            // Paths that changed between being a file and another kind of entry were modified.
+            let replaced: Vec<String> = update
+                .added_files
+                .intersection(&update.removed_files)
+                .cloned()
+                .collect();
+
+            for path in replaced {
+                update.added_files.remove(&path);
+                update.removed_files.remove(&path);
+                update.modified_files.insert(path);
+            }
+        }
+
+        Ok(update)
+    }
+}
+
+impl ChecksumSetUpdate {
+    pub fn is_changed(&self) -> bool {
+        !self.added_files.is_empty() || !self.modified_files.is_empty() || !self.removed_files.is_empty()
+    }
+
+    pub fn print(&self) {
+        let mut summary: Vec<String> = Vec::new();
+
+        println!();
+
+        if !self.added_files.is_empty() {
+            println!("-- ADDED FILES --");
+
+            for p in self.added_files.iter() {
+                println!("{}", p);
+            }
+
+            println!();
+
+            summary.push(format!("{} added files.", self.added_files.len()));
+        }
+
+        if !self.modified_files.is_empty() {
+            println!("-- MODIFIED FILES --");
+
+            for p in self.modified_files.iter() {
+                println!("{}", p);
+            }
+
+            println!();
+
+            summary.push(format!("{} modified files.", self.modified_files.len()));
+        }
+
+        if !self.removed_files.is_empty() {
+            println!("-- REMOVED FILES --");
+
+            for p in self.removed_files.iter() {
+                println!("{}", p);
+            }
+
+            println!();
+
+            summary.push(format!("{} removed files.", self.removed_files.len()));
+        }
+
+        if !self.unreadable_files.is_empty() {
+            println!("-- UNREADABLE FILES --");
+
+            for p in self.unreadable_files.iter() {
+                println!("{}", p);
+            }
+
+            println!();
+
+            summary.push(format!("{} unreadable files.", self.unreadable_files.len()));
+        }
+
+        for line in summary.iter() {
+            println!("{}", line);
+        }
+    }
+}
@@ -1,15 +1,95 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
-    path::Path,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
-use crate::ui::UiHandler;
+use tracing::warn;
 
-use super::{ChecksumSet, ChecksumSetDiff};
+use crate::{ui::UiHandler, util, Error};
+
+use super::{
+    checkpoint::{Checkpoint, CheckpointOperation},
+    diff, metadata,
+    pool::{self, HashJob},
+    scan, CheckpointOptions, ChecksumSet, ChecksumSetDiff, EntryKind, FileInfo, HashType, MetadataKind,
+};
+
+#[derive(Debug)]
+pub struct VerifyOptions {
+    /// Number of worker threads used to hash files.
+    pub threads: usize,
+    /// Hash types to verify. If empty, all hash types in the checksum set are verified.
+    pub hash_types: Vec<HashType>,
+    /// Scan the root path for files not present in the checksum set.
+    pub find_additional_files: bool,
+    /// Hash additional files, to detect whether they are missing files that have been moved.
+    pub hash_additional_files: bool,
+    /// Kinds of metadata not to compare, even if captured in the checksum set.
+    pub ignore_metadata: BTreeSet<MetadataKind>,
+    /// Periodically save the progress of verification to a checkpoint file,
+    /// optionally resuming from an existing one.
+    pub checkpoint: Option<CheckpointOptions>,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            threads: pool::default_thread_count(),
+            hash_types: Vec::new(),
+            find_additional_files: true,
+            hash_additional_files: false,
+            ignore_metadata: BTreeSet::new(),
+            checkpoint: None,
+        }
+    }
+}
+
+/// Files and other entries found on disk, but not present in a checksum set.
+#[derive(Default)]
+struct AdditionalFiles {
+    /// Relative paths of files, mapped to their actual path and size.
+    files: BTreeMap<String, (PathBuf, u64)>,
+    /// Relative paths of other entries, mapped to their kind.
+    entries: BTreeMap<String, EntryKind>,
+}
 
 impl ChecksumSet {
-    pub fn verify(&self, root_path: &Path, ui: &mut dyn UiHandler) -> Result<ChecksumSetDiff, anyhow::Error> {
-        let hash_type = self.hash_type;
+    pub fn verify(
+        &self,
+        root_path: &Path,
+        options: &VerifyOptions,
+        ui: &mut dyn UiHandler,
+    ) -> Result<ChecksumSetDiff, Error> {
+        self.verify_scan_paths(root_path, &self.scan_paths(), options, ui)
+    }
+
+    /// Verify the checksum set, scanning the specified paths for additional files.
+    pub(super) fn verify_scan_paths(
+        &self,
+        root_path: &Path,
+        scan_paths: &[String],
+        options: &VerifyOptions,
+        ui: &mut dyn UiHandler,
+    ) -> Result<ChecksumSetDiff, Error> {
+        let hash_types: Vec<HashType> = if options.hash_types.is_empty() {
+            self.hash_types.clone()
+        } else {
+            if let Some(hash_type) = options.hash_types.iter().find(|ht| !self.hash_types.contains(ht)) {
+                return Err(Error::MissingHashType(*hash_type));
+            }
+
+            options.hash_types.clone()
+        };
+
+        let AdditionalFiles {
+            files: found_additional_files,
+            entries: additional_entries,
+        } = if options.find_additional_files {
+            self.find_additional_files(root_path, scan_paths, ui)?
+        } else {
+            AdditionalFiles::default()
+        };
 
         ui.begin_prepare();
 
@@ -24,43 +104,245 @@ impl ChecksumSet {
             })
             .collect();
 
-        let total_size: u64 = files.iter().map(|(_, _, size, _)| size).sum();
+        // Only additional files with the same size as a missing file can possibly be one that was moved.
+        let moved_candidates: Vec<(&String, &PathBuf, u64)> = if options.hash_additional_files {
+            let missing_sizes: BTreeSet<Option<u64>> = files
+                .iter()
+                .filter(|(_, actual_path, _, _)| self.entry_metadata(actual_path).is_err())
+                .map(|(_, _, _, fi)| fi.size)
+                .collect();
+
+            found_additional_files
+                .iter()
+                .filter(|(_, (_, size))| missing_sizes.contains(&None) || missing_sizes.contains(&Some(*size)))
+                .map(|(path, (actual_path, size))| (path, actual_path, *size))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let total_size: u64 = files.iter().map(|(_, _, size, _)| size).sum::<u64>()
+            + moved_candidates.iter().map(|(_, _, size)| size).sum::<u64>();
 
         ui.end_prepare();
 
-        ui.begin_verify(files.len() as u32, total_size);
+        ui.begin_verify((files.len() + moved_candidates.len()) as u32, total_size);
+
+        let metadata_kinds: BTreeSet<MetadataKind> =
+            self.metadata.difference(&options.ignore_metadata).copied().collect();
+
+        // Metadata and entry differences are recorded directly in the resulting diff.
+        let mut metadata_diff = ChecksumSetDiff {
+            additional_entries,
+            ..Default::default()
+        };
 
         let mut missing_files: BTreeSet<String> = Default::default();
         let mut differing_sizes: BTreeMap<String, (u64, u64)> = BTreeMap::new();
         let mut differing_hashes: BTreeMap<String, (String, String)> = Default::default();
 
-        for (path, actual_path, size, fi) in files.into_iter() {
-            if !actual_path.exists() {
+        let mut checkpoint = match &options.checkpoint {
+            Some(checkpoint_options) => Some(Checkpoint::begin(
+                checkpoint_options,
+                CheckpointOperation::Verify,
+                root_path,
+                &hash_types,
+                // Serializing a checksum set cannot fail.
+                HashType::Sha256.hash_bytes(&serde_json::to_vec(self).unwrap_or_default()),
+            )?),
+            None => None,
+        };
+
+        let mut to_hash: Vec<(&String, &FileInfo, Option<i64>)> = Vec::new();
+        let mut jobs: Vec<HashJob> = Vec::new();
+
+        for (index, (path, actual_path, size, fi)) in files.iter().enumerate() {
+            let Ok(entry_metadata) = self.entry_metadata(actual_path) else {
                 missing_files.insert(path.to_string());
                 continue;
+            };
+
+            if let Some(kind) = EntryKind::from_file_type(entry_metadata.file_type()).filter(|k| *k != EntryKind::File)
+            {
+                metadata_diff
+                    .differing_kinds
+                    .insert(path.to_string(), (EntryKind::File, kind));
+
+                ui.file_progress(index, *size);
+                continue;
             }
 
-            if size != fi.size {
-                differing_sizes.insert(path.to_string(), (fi.size, size));
+            if !metadata_kinds.is_empty() {
+                if let Ok(metadata) = std::fs::metadata(actual_path) {
+                    let actual_fi = FileInfo::from_metadata(actual_path, &metadata, &metadata_kinds);
+
+                    metadata::compare_metadata(path, fi, &actual_fi, &metadata_kinds, &mut metadata_diff);
+                }
+            }
 
-                ui.file_progress(size);
+            if let Some(expected_size) = fi.size.filter(|s| s != size) {
+                differing_sizes.insert(path.to_string(), (expected_size, *size));
+
+                ui.file_progress(index, *size);
                 continue;
             }
 
-            let hash = hash_type.hash_file(&actual_path, ui)?;
+            // Modification times are only needed to tell whether files hashed by a resumed run have changed since.
+            let mtime = checkpoint
+                .as_ref()
+                .and_then(|_| std::fs::metadata(actual_path).ok())
+                .and_then(|m| util::modified_nanos(&m));
+
+            if let Some(hashes) = checkpoint.as_ref().and_then(|cp| cp.hashes(path, *size, mtime)) {
+                if let Some(differing) = diff::compare_hashes(&fi.hashes, hashes, &hash_types) {
+                    differing_hashes.insert(path.to_string(), differing);
+                }
 
-            if hash != fi.hash {
-                differing_hashes.insert(path.to_string(), (fi.hash.clone(), hash));
+                ui.file_progress(index, *size);
+                continue;
+            }
+
+            to_hash.push((*path, *fi, mtime));
+            jobs.push(HashJob {
+                path: actual_path,
+                size: *size,
+            });
+        }
+
+        let mut unreadable_files: BTreeSet<String> = Default::default();
+
+        let results = pool::hash_files_with(&hash_types, &jobs, options.threads, ui, |index, result| {
+            if let (Some(checkpoint), Ok(hashes)) = (checkpoint.as_mut(), result) {
+                let (path, _, mtime) = to_hash[index];
+
+                checkpoint.record(path, jobs[index].size, mtime, hashes);
+            }
+        });
+
+        if let Some(checkpoint) = checkpoint.as_mut() {
+            checkpoint.finish();
+        }
+
+        for ((path, fi, _), result) in to_hash.into_iter().zip(results) {
+            match result {
+                Ok(hashes) => {
+                    if let Some(differing) = diff::compare_hashes(&fi.hashes, &hashes, &hash_types) {
+                        differing_hashes.insert(path.to_string(), differing);
+                    }
+                }
+                Err(err) => {
+                    warn!("{err}");
+                    unreadable_files.insert(path.to_string());
+                }
+            }
+        }
+
+        let mut additional_files: BTreeSet<String> = found_additional_files.keys().cloned().collect();
+        let mut moved_files: BTreeMap<String, String> = BTreeMap::new();
+
+        let jobs: Vec<HashJob> = moved_candidates
+            .iter()
+            .map(|(_, actual_path, size)| HashJob {
+                path: actual_path,
+                size: *size,
+            })
+            .collect();
+
+        let results = pool::hash_files(&hash_types, &jobs, options.threads, ui);
+
+        for ((path, _, size), result) in moved_candidates.into_iter().zip(results) {
+            let hashes = match result {
+                Ok(hashes) => hashes,
+                Err(err) => {
+                    warn!("{err}");
+                    continue;
+                }
+            };
+
+            let original_path = missing_files
+                .iter()
+                .find(|p| {
+                    let fi = &self.files[*p];
+
+                    fi.size.is_none_or(|s| s == size)
+                        && diff::compare_hashes(&fi.hashes, &hashes, &hash_types).is_none()
+                })
+                .cloned();
+
+            if let Some(original_path) = original_path {
+                missing_files.remove(&original_path);
+                additional_files.remove(path);
+                moved_files.insert(original_path, path.clone());
             }
         }
 
         ui.end_verify();
 
+        if let Some(entries) = &self.entries {
+            for (path, entry) in entries.iter() {
+                if !self.verify_entry(path, entry, &root_path.join(path), &mut metadata_diff) {
+                    metadata_diff.missing_entries.insert(path.clone(), entry.kind());
+                }
+            }
+        }
+
         Ok(ChecksumSetDiff {
-            additional_files: Default::default(),
+            hash_types,
+            additional_files,
             missing_files,
             differing_sizes,
             differing_hashes,
+            unreadable_files,
+            moved_files,
+            ..metadata_diff
         })
     }
+
+    /// Scan the specified paths for files and other entries not present in this checksum set.
+    ///
+    /// Entries other than files are only returned if the checksum set records them.
+    fn find_additional_files(
+        &self,
+        root_path: &Path,
+        scan_paths: &[String],
+        ui: &mut dyn UiHandler,
+    ) -> Result<AdditionalFiles, Error> {
+        let filter = Arc::new(self.filter.compile()?);
+
+        ui.begin_scan();
+
+        let scanned = scan::scan_relative(root_path, scan_paths, &filter, self.follow_symlinks);
+
+        let is_known = |rel_path: &String| {
+            self.files.contains_key(rel_path)
+                || self.unreadable_files.contains(rel_path)
+                || self.entries.as_ref().is_some_and(|e| e.contains_key(rel_path))
+        };
+
+        let files: BTreeMap<String, (PathBuf, u64)> = scanned
+            .files
+            .into_iter()
+            .filter(|(rel_path, _)| !is_known(rel_path))
+            .map(|(rel_path, file_path)| {
+                let size = std::fs::metadata(&file_path).map(|m| m.len()).unwrap_or(0);
+
+                (rel_path, (file_path, size))
+            })
+            .collect();
+
+        let entries: BTreeMap<String, EntryKind> = if self.entries.is_some() {
+            scanned
+                .entries
+                .into_iter()
+                .filter(|(rel_path, _)| !is_known(rel_path))
+                .map(|(rel_path, entry)| (rel_path, entry.kind()))
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
+
+        ui.end_scan();
+
+        Ok(AdditionalFiles { files, entries })
+    }
 }
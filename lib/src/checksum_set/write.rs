@@ -1,16 +1,207 @@
-use std::{fs, path::Path};
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
 
-use anyhow::Context;
+use crate::{util, Error};
 
-use super::ChecksumSet;
+use super::{format, ChecksumSet, ChecksumSetFormat, HashType};
+
+#[derive(Debug, Default)]
+pub struct WriteOptions {
+    /// For formats that only support a single hash type, which one to write.
+    /// If not specified, it is determined by the file name if possible,
+    /// or the checksum set's preferred hash type otherwise.
+    pub hash_type: Option<HashType>,
+    /// Keep the previous version of the file, if any, with a timestamp suffix.
+    pub backup: bool,
+}
+
+/// Checksum set format, along with the hash types to write.
+enum Output {
+    Kecs,
+    Sfv,
+    Sum { hash_type: HashType, bsd: bool },
+    Hashdeep { hash_types: Vec<HashType> },
+}
 
 impl ChecksumSet {
-    pub fn write_file(&self, path: &Path) -> Result<(), anyhow::Error> {
-        let file = fs::File::create(path).with_context(|| format!("Creating output file: {}", path.display()))?;
+    /// Write the checksum set to a file, in a format determined by the file name.
+    ///
+    /// Files whose format cannot be determined from their name are written in KeCS format.
+    pub fn write_file(&self, path: &Path, options: &WriteOptions) -> Result<Option<PathBuf>, Error> {
+        let format = ChecksumSetFormat::from_path(path).unwrap_or(ChecksumSetFormat::Kecs);
+
+        self.write_file_as(path, format, options)
+    }
+
+    /// Write the checksum set to a file in the specified format.
+    ///
+    /// The checksum set is checked to be representable in the format before anything is
+    /// written, and the file is replaced atomically, so that it is never left partially written.
+    /// Returns the path of the previous version's backup, if one was made.
+    pub fn write_file_as(
+        &self,
+        path: &Path,
+        format: ChecksumSetFormat,
+        options: &WriteOptions,
+    ) -> Result<Option<PathBuf>, Error> {
+        let output = self.output(path, format, options.hash_type)?;
+
+        util::write_file_atomic(path, options.backup, |writer| self.write_output(&output, writer)).map_err(|source| {
+            Error::Write {
+                path: path.to_path_buf(),
+                source,
+            }
+        })
+    }
+
+    /// Write the checksum set to a writer in the specified format, such as standard output.
+    ///
+    /// `path` is only used in error messages, and to determine which hash type to write
+    /// the same way as when writing to a file. It may be `-` for standard output.
+    pub fn write_to<W: Write>(
+        &self,
+        mut writer: W,
+        path: &Path,
+        format: ChecksumSetFormat,
+        hash_type: Option<HashType>,
+    ) -> Result<(), Error> {
+        let output = self.output(path, format, hash_type)?;
+
+        self.write_output(&output, &mut writer)
+            .and_then(|_| match output {
+                // Unlike the other formats, JSON does not end with a newline.
+                Output::Kecs => writeln!(writer),
+                _ => Ok(()),
+            })
+            .and_then(|_| writer.flush())
+            .map_err(|source| Error::Write {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+
+    fn write_output<W: Write>(&self, output: &Output, writer: W) -> Result<(), io::Error> {
+        if !matches!(output, Output::Kecs) {
+            self.warn_unrepresentable();
+        }
+
+        match output {
+            Output::Kecs => serde_json::to_writer_pretty(writer, self).map_err(io::Error::from),
+            Output::Sfv => format::write_sfv_file(self, writer),
+            Output::Sum { hash_type, bsd } => format::write_sum_file(self, *hash_type, *bsd, writer),
+            Output::Hashdeep { hash_types } => format::write_hashdeep_file(self, hash_types, writer),
+        }
+    }
+
+    /// Determine how to write the checksum set in the specified format,
+    /// failing if it cannot be represented in it.
+    fn output(&self, path: &Path, format: ChecksumSetFormat, hash_type: Option<HashType>) -> Result<Output, Error> {
+        match format {
+            ChecksumSetFormat::Kecs => Ok(Output::Kecs),
+            ChecksumSetFormat::Sfv => {
+                if !self.hash_types.contains(&HashType::Crc32) {
+                    return Err(unrepresentable(format, "SFV files require crc32 hashes."));
+                }
+
+                if hash_type.is_some_and(|ht| ht != HashType::Crc32) {
+                    return Err(unrepresentable(format, "SFV files only support crc32 hashes."));
+                }
+
+                Ok(Output::Sfv)
+            }
+            ChecksumSetFormat::Gnu | ChecksumSetFormat::Bsd => Ok(Output::Sum {
+                hash_type: self.output_hash_type(path, hash_type)?,
+                bsd: format == ChecksumSetFormat::Bsd,
+            }),
+            ChecksumSetFormat::Hashdeep => {
+                let hash_types = self.hashdeep_hash_types(hash_type)?;
 
-        serde_json::to_writer_pretty(file, self)
-            .with_context(|| format!("Writing to output file: {}", path.display()))?;
+                if let Some(path) = self.files.iter().find(|(_, fi)| fi.size.is_none()).map(|(p, _)| p) {
+                    return Err(unrepresentable(
+                        format,
+                        format!("Hashdeep files require file sizes, but the size of '{path}' is unknown."),
+                    ));
+                }
+
+                Ok(Output::Hashdeep { hash_types })
+            }
+        }
+    }
+
+    /// Determine which hash type to write to a file that only supports a single one.
+    fn output_hash_type(&self, path: &Path, hash_type: Option<HashType>) -> Result<HashType, Error> {
+        let hash_type = hash_type
+            .or_else(|| {
+                path.file_name()
+                    .and_then(|n| HashType::from_filename(&n.to_string_lossy()))
+            })
+            .or_else(|| self.hash_types.first().copied())
+            .ok_or(Error::NoHashTypes)?;
+
+        if !self.hash_types.contains(&hash_type) {
+            return Err(Error::MissingHashType(hash_type));
+        }
+
+        Ok(hash_type)
+    }
+
+    /// Determine which hash types to write to a hashdeep file.
+    ///
+    /// If `hash_type` is not specified, all hash types supported by hashdeep are written.
+    fn hashdeep_hash_types(&self, hash_type: Option<HashType>) -> Result<Vec<HashType>, Error> {
+        if let Some(hash_type) = hash_type {
+            if !format::HASHDEEP_HASH_TYPES.contains(&hash_type) {
+                return Err(unrepresentable(
+                    ChecksumSetFormat::Hashdeep,
+                    format!("Hashdeep files do not support {hash_type} hashes."),
+                ));
+            }
+
+            if !self.hash_types.contains(&hash_type) {
+                return Err(Error::MissingHashType(hash_type));
+            }
+
+            return Ok(vec![hash_type]);
+        }
+
+        let hash_types: Vec<HashType> = format::HASHDEEP_HASH_TYPES
+            .iter()
+            .filter(|ht| self.hash_types.contains(ht))
+            .copied()
+            .collect();
+
+        if hash_types.is_empty() {
+            return Err(unrepresentable(
+                ChecksumSetFormat::Hashdeep,
+                "Hashdeep files require md5, sha1 or sha256 hashes.",
+            ));
+        }
+
+        Ok(hash_types)
+    }
+
+    fn warn_unrepresentable(&self) {
+        if !self.unreadable_files.is_empty() {
+            tracing::warn!(
+                "{} unreadable files cannot be represented in the output format, and will be omitted.",
+                self.unreadable_files.len()
+            );
+        }
+
+        if let Some(entries) = self.entries.as_ref().filter(|e| !e.is_empty()) {
+            tracing::warn!(
+                "{} symlinks, directories and special files cannot be represented in the output format, and will be omitted.",
+                entries.len()
+            );
+        }
+    }
+}
 
-        Ok(())
+fn unrepresentable(format: ChecksumSetFormat, reason: impl Into<String>) -> Error {
+    Error::Unrepresentable {
+        format,
+        reason: reason.into(),
     }
 }
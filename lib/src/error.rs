@@ -0,0 +1,92 @@
+use std::{fmt, io, path::PathBuf};
+
+use crate::checksum_set::{ChecksumSetFormat, HashType};
+
+/// Error returned by operations in the library.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read.
+    Read { path: PathBuf, source: io::Error },
+    /// A file could not be written.
+    Write { path: PathBuf, source: io::Error },
+    /// A KeCS checksum set or checkpoint file could not be deserialized.
+    Deserialize { path: PathBuf, source: serde_json::Error },
+    /// A checksum file in another format is malformed.
+    Parse { path: PathBuf, message: String },
+    /// A filter pattern is invalid.
+    InvalidPattern { pattern: String, message: String },
+    /// The checksum set does not contain any hash types.
+    NoHashTypes,
+    /// The checksum set does not contain hashes of the specified type.
+    MissingHashType(HashType),
+    /// The checksum sets being compared or combined have no hash types in common.
+    NoCommonHashTypes,
+    /// The checksum sets being compared have no hash types in common,
+    /// and no root path was specified to rehash either one's files.
+    RehashRootRequired,
+    /// The checksum sets being combined have different hash types.
+    MixedHashTypes { a: Vec<HashType>, b: Vec<HashType> },
+    /// The checksum set cannot be represented in the output format.
+    Unrepresentable { format: ChecksumSetFormat, reason: String },
+    /// The size of a file is unknown, but required by the operation.
+    UnknownSize { path: String },
+    /// A file does not have a hash of the type required by the operation.
+    MissingHash { path: String, hash_type: HashType },
+    /// A path is both a file and a directory.
+    PathConflict { path: String },
+    /// A path has different contents in the checksum sets being merged.
+    MergeConflict { path: String },
+    /// A checkpoint cannot be used to resume the current run.
+    CheckpointMismatch { path: PathBuf, reason: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read { path, .. } => write!(f, "Reading file: {}", path.display()),
+            Self::Write { path, .. } => write!(f, "Writing file: {}", path.display()),
+            Self::Deserialize { path, .. } => write!(f, "Deserializing file: {}", path.display()),
+            Self::Parse { path, message } => write!(f, "Parsing checksum file: {}: {message}", path.display()),
+            Self::InvalidPattern { pattern, message } => write!(f, "Invalid pattern: {pattern}: {message}"),
+            Self::NoHashTypes => write!(f, "Checksum set does not contain any hash types."),
+            Self::MissingHashType(hash_type) => write!(f, "Checksum set does not contain {hash_type} hashes."),
+            Self::NoCommonHashTypes => write!(f, "Checksum sets have no hash types in common."),
+            Self::RehashRootRequired => write!(
+                f,
+                "Checksum sets have no hash types in common, and no root path was specified to rehash either one's files."
+            ),
+            Self::MixedHashTypes { a, b } => write!(
+                f,
+                "Checksum sets have different hash types (A: {} / B: {}).",
+                join_hash_types(a),
+                join_hash_types(b)
+            ),
+            Self::Unrepresentable { reason, .. } => write!(f, "{reason}"),
+            Self::UnknownSize { path } => write!(f, "The size of '{path}' is unknown."),
+            Self::MissingHash { path, hash_type } => write!(f, "'{path}' does not have a {hash_type} hash."),
+            Self::PathConflict { path } => write!(f, "'{path}' is both a file and a directory."),
+            Self::MergeConflict { path } => write!(f, "'{path}' differs between the checksum sets."),
+            Self::CheckpointMismatch { path, reason } => {
+                write!(f, "Cannot resume from checkpoint: {}: {reason}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read { source, .. } | Self::Write { source, .. } => Some(source),
+            Self::Deserialize { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+fn join_hash_types(hash_types: &[HashType]) -> String {
+    hash_types
+        .iter()
+        .map(|ht| ht.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,3 +1,9 @@
 pub mod checksum_set;
+mod error;
 pub mod ui;
 pub mod util;
+
+#[cfg(test)]
+mod test_util;
+
+pub use self::error::Error;
@@ -0,0 +1,108 @@
+//! Helpers shared by the unit tests.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    checksum_set::{ChecksumSet, FileInfo, HashType},
+    ui::UiHandler,
+};
+
+/// Create a checksum set in memory, with files of the specified paths and contents.
+pub(crate) fn checksum_set(hash_types: &[HashType], files: &[(&str, &str)]) -> ChecksumSet {
+    ChecksumSet {
+        hash_types: hash_types.to_vec(),
+        files: files
+            .iter()
+            .map(|(path, content)| {
+                let fi = FileInfo {
+                    size: Some(content.len() as u64),
+                    hashes: hash_types
+                        .iter()
+                        .map(|ht| (*ht, ht.hash_bytes(content.as_bytes())))
+                        .collect(),
+                    ..Default::default()
+                };
+
+                (path.to_string(), fi)
+            })
+            .collect(),
+        unreadable_files: Default::default(),
+        scan_paths: vec![String::new()],
+        filter: Default::default(),
+        metadata: Default::default(),
+        entries: None,
+        follow_symlinks: false,
+    }
+}
+
+/// UI handler that ignores all progress.
+pub(crate) struct NullUi;
+
+impl UiHandler for NullUi {
+    fn begin_load(&mut self, _filename: &str) {}
+    fn end_load(&mut self) {}
+
+    fn begin_diff(&mut self) {}
+    fn end_diff(&mut self) {}
+
+    fn begin_scan(&mut self) {}
+    fn end_scan(&mut self) {}
+
+    fn begin_prepare(&mut self) {}
+    fn end_prepare(&mut self) {}
+
+    fn begin_generate(&mut self, _file_count: u32, _total_size: u64) {}
+    fn end_generate(&mut self) {}
+
+    fn begin_verify(&mut self, _file_count: u32, _total_size: u64) {}
+    fn end_verify(&mut self) {}
+
+    fn begin_file(&mut self, _id: usize, _filename: &str, _size: u64) {}
+    fn file_progress(&mut self, _id: usize, _bytes: u64) {}
+    fn end_file(&mut self, _id: usize) {}
+}
+
+/// Temporary directory, removed when dropped.
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "kecs-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        fs::create_dir_all(&path).unwrap();
+
+        Self { path }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write a file relative to the directory, creating any parent directories.
+    pub(crate) fn write(&self, rel_path: &str, content: &str) -> PathBuf {
+        let path = self.path.join(rel_path);
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -17,7 +17,9 @@ pub trait UiHandler {
     fn begin_verify(&mut self, file_count: u32, total_size: u64);
     fn end_verify(&mut self);
 
-    fn begin_file(&mut self, filename: &str, size: u64);
-    fn file_progress(&mut self, bytes: u64);
-    fn end_file(&mut self);
+    /// Files may be processed concurrently, so each file is identified by an id
+    /// that is unique among the files currently being processed.
+    fn begin_file(&mut self, id: usize, filename: &str, size: u64);
+    fn file_progress(&mut self, id: usize, bytes: u64);
+    fn end_file(&mut self, id: usize);
 }
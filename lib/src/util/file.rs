@@ -0,0 +1,196 @@
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+use super::format_timestamp_compact;
+
+/// Counter making the names of temporary files unique within the process.
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Write a file atomically.
+///
+/// The contents are written to a temporary file in the same directory, which is synced
+/// to disk and renamed over the destination once complete, so that the destination
+/// is never left partially written. The temporary file is removed if writing fails.
+///
+/// If `backup` is set and the destination already exists, its previous version is kept
+/// next to it with a timestamp suffix. Returns the path of the backup, if one was made.
+pub fn write_file_atomic<F>(path: &Path, backup: bool, write: F) -> Result<Option<PathBuf>, io::Error>
+where
+    F: FnOnce(&mut BufWriter<fs::File>) -> Result<(), io::Error>,
+{
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path does not have a file name."))?
+        .to_string_lossy();
+
+    // The process ID and a counter make the name unique, so that no other file is written to.
+    let temp_path = path.with_file_name(format!(
+        ".{file_name}.{}-{}.kecs-tmp",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    // If the temporary file cannot be created, such as if a file already exists at its path,
+    // there is nothing to clean up. Anything already at the path must be left alone.
+    let file = fs::OpenOptions::new().write(true).create_new(true).open(&temp_path)?;
+
+    let result = write_temp_file(file, path, write).and_then(|_| {
+        let backup_path = if backup { backup_file(path)? } else { None };
+
+        fs::rename(&temp_path, path)?;
+
+        Ok(backup_path)
+    });
+
+    match result {
+        Ok(backup_path) => {
+            sync_parent_dir(path);
+
+            Ok(backup_path)
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&temp_path);
+
+            Err(err)
+        }
+    }
+}
+
+/// Write and sync a newly created temporary file, with the same permissions as the destination if it exists.
+fn write_temp_file<F>(file: fs::File, path: &Path, write: F) -> Result<(), io::Error>
+where
+    F: FnOnce(&mut BufWriter<fs::File>) -> Result<(), io::Error>,
+{
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+    }
+
+    let mut writer = BufWriter::new(file);
+
+    write(&mut writer)?;
+
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
+
+/// Keep the current version of a file next to it, with a timestamp suffix.
+///
+/// The backup is made by hardlinking the file if possible, or copying it otherwise.
+fn backup_file(path: &Path) -> Result<Option<PathBuf>, io::Error> {
+    if fs::symlink_metadata(path).is_err() {
+        return Ok(None);
+    }
+
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+
+    let timestamp = format_timestamp_compact(secs);
+
+    // If several backups are made within the same second, number them.
+    let backup_path = (1..)
+        .map(|n| {
+            let mut backup_name = path.file_name().unwrap_or_default().to_os_string();
+
+            match n {
+                1 => backup_name.push(format!(".{timestamp}")),
+                n => backup_name.push(format!(".{timestamp}-{n}")),
+            }
+
+            path.with_file_name(backup_name)
+        })
+        .find(|p| fs::symlink_metadata(p).is_err())
+        .unwrap_or_default();
+
+    if fs::hard_link(path, &backup_path).is_err() {
+        fs::copy(path, &backup_path)?;
+    }
+
+    Ok(Some(backup_path))
+}
+
+/// Sync the directory containing a file, so that a rename into it is durable.
+///
+/// This is only possible on Unix, and any errors are ignored.
+fn sync_parent_dir(path: &Path) {
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        if let Ok(dir) = fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TempDir;
+
+    use super::*;
+
+    fn dir_entries(dir: &TempDir) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn write_replaces_file() {
+        let dir = TempDir::new();
+        let path = dir.write("file", "old");
+
+        let backup_path = write_file_atomic(&path, false, |w| w.write_all(b"new")).unwrap();
+
+        assert_eq!(backup_path, None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(dir_entries(&dir), vec!["file"]);
+    }
+
+    #[test]
+    fn write_keeps_numbered_backups() {
+        let dir = TempDir::new();
+        let path = dir.write("file", "1");
+
+        let first = write_file_atomic(&path, true, |w| w.write_all(b"2")).unwrap().unwrap();
+        let second = write_file_atomic(&path, true, |w| w.write_all(b"3")).unwrap().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "3");
+        assert_eq!(fs::read_to_string(&first).unwrap(), "1");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "2");
+
+        // Backups made within the same second are numbered, rather than overwriting each other.
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn failed_write_leaves_destination_and_removes_temp_file() {
+        let dir = TempDir::new();
+        let path = dir.write("file", "old");
+
+        let result = write_file_atomic(&path, true, |w| {
+            w.write_all(b"partial")?;
+
+            Err(io::Error::other("failed"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert_eq!(dir_entries(&dir), vec!["file"]);
+    }
+}
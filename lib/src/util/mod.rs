@@ -1,5 +1,7 @@
+mod file;
 mod path;
 mod time;
 
+pub use self::file::*;
 pub use self::path::*;
 pub use self::time::*;
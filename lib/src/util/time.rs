@@ -1,4 +1,7 @@
-use std::time::Duration;
+use std::{
+    fs::Metadata,
+    time::{Duration, UNIX_EPOCH},
+};
 
 pub fn humanize_duration(duration: Duration) -> String {
     let mut words: Vec<String> = Vec::new();
@@ -22,3 +25,62 @@ pub fn humanize_duration(duration: Duration) -> String {
 
     words.join(" ")
 }
+
+/// Get the modification time from file metadata, in nanoseconds since the Unix epoch.
+pub fn modified_nanos(metadata: &Metadata) -> Option<i64> {
+    let modified = metadata.modified().ok()?;
+
+    Some(match modified.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_nanos() as i64,
+        Err(err) => -(err.duration().as_nanos() as i64),
+    })
+}
+
+/// Format a timestamp in nanoseconds since the Unix epoch as a UTC date and time.
+pub fn format_timestamp_nanos(nanos: i64) -> String {
+    let secs = nanos.div_euclid(1_000_000_000);
+    let subsec_nanos = nanos.rem_euclid(1_000_000_000);
+
+    let (year, month, day, secs_of_day) = civil_from_secs(secs);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}.{subsec_nanos:09} UTC",
+        secs_of_day / 3600,
+        (secs_of_day % 3600) / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Format a timestamp in seconds since the Unix epoch as a compact UTC date and time
+/// suitable for use in file names, such as `20240131T235959Z`.
+pub fn format_timestamp_compact(secs: i64) -> String {
+    let (year, month, day, secs_of_day) = civil_from_secs(secs);
+
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        secs_of_day / 3600,
+        (secs_of_day % 3600) / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Convert a timestamp in seconds since the Unix epoch to a UTC year, month,
+/// day and number of seconds into the day.
+fn civil_from_secs(secs: i64) -> (i64, i64, i64, i64) {
+    let days = secs.div_euclid(86400);
+    let secs_of_day = secs.rem_euclid(86400);
+
+    // Convert days since the epoch to a civil date.
+    // See: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day, secs_of_day)
+}
@@ -0,0 +1,109 @@
+//! Known-answer tests for the hash types, using the test vectors published with each algorithm.
+
+use std::str::FromStr;
+
+use kecs::checksum_set::HashType;
+
+const MESSAGES: &[&[u8]] = &[b"", b"abc"];
+
+/// Expected hashes of each message in `MESSAGES`.
+const VECTORS: &[(HashType, &[&str])] = &[
+    (
+        HashType::Sha224,
+        &[
+            "d14a028c2a3a2bc9476102bb288234c415a2b01f828ea62ac5b3e42f",
+            "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7",
+        ],
+    ),
+    (
+        HashType::Sha384,
+        &[
+            "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b",
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+        ],
+    ),
+    (
+        HashType::Sha512,
+        &[
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+        ],
+    ),
+    (
+        HashType::Sha3_512,
+        &[
+            "a69f73cca23a9ac5c8b567dc185a756e97c982164fe25859e0d1dcc1475c80a615b2123af1f5f94c11e3e9402c3ac558f500199d95b6d3e301758586281dcd26",
+            "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0",
+        ],
+    ),
+    (
+        HashType::Blake2b256,
+        &[
+            "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8",
+            "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319",
+        ],
+    ),
+];
+
+#[test]
+fn hash_bytes_matches_known_answers() {
+    for (hash_type, expected) in VECTORS {
+        for (message, expected) in MESSAGES.iter().zip(expected.iter()) {
+            assert_eq!(hash_type.hash_bytes(message), *expected, "{hash_type} of {message:?}");
+        }
+    }
+}
+
+#[test]
+fn incremental_hashing_matches_known_answers() {
+    for (hash_type, expected) in VECTORS {
+        let mut hasher = hash_type.hasher();
+
+        hasher.update(b"a");
+        hasher.update(b"");
+        hasher.update(b"bc");
+
+        assert_eq!(hasher.finalize(), expected[1], "{hash_type}");
+    }
+}
+
+#[test]
+fn hash_reader_many_matches_known_answers() {
+    let hash_types: Vec<HashType> = VECTORS.iter().map(|(ht, _)| *ht).collect();
+    let hashes = HashType::hash_reader_many(&hash_types, &b"abc"[..]).unwrap();
+
+    for ((hash_type, expected), hash) in VECTORS.iter().zip(hashes.iter()) {
+        assert_eq!(hash, expected[1], "{hash_type}");
+    }
+}
+
+#[test]
+fn hash_type_names_round_trip() {
+    for (name, hash_type) in [
+        ("sha224", HashType::Sha224),
+        ("sha384", HashType::Sha384),
+        ("sha512", HashType::Sha512),
+        ("sha3_512", HashType::Sha3_512),
+        ("blake2b256", HashType::Blake2b256),
+    ] {
+        assert_eq!(HashType::from_str(name).unwrap(), hash_type);
+        assert_eq!(hash_type.to_string(), name);
+        assert_eq!(serde_json::to_string(&hash_type).unwrap(), format!("\"{name}\""));
+    }
+}
+
+#[test]
+fn hash_type_from_filename() {
+    for (filename, hash_type) in [
+        ("SHA224SUMS", HashType::Sha224),
+        ("SHA384SUMS", HashType::Sha384),
+        ("SHA512SUMS", HashType::Sha512),
+        ("SHA3-512SUMS", HashType::Sha3_512),
+        ("files.sha3_512", HashType::Sha3_512),
+        ("BLAKE2B-256SUMS", HashType::Blake2b256),
+        ("files.blake2b256", HashType::Blake2b256),
+        ("files.blake2b", HashType::Blake2b512),
+    ] {
+        assert_eq!(HashType::from_filename(filename), Some(hash_type), "{filename}");
+    }
+}